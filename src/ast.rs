@@ -1,28 +1,170 @@
-use crate::database::ItemId;
+use std::ops::Range;
+
+use crate::database::{ItemId, ItemKind, Visibility, ROOT_NAME};
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct UnresolvedIdent {
-    pub parts: Vec<String>,
+    // Each part along with the span of its token.
+    pub parts: Vec<(String, Range<usize>)>,
+    pub span: Range<usize>,
 }
 
-impl std::fmt::Debug for UnresolvedIdent {
+impl UnresolvedIdent {
+    // The first `len` parts, dotted.
+    pub fn prefix(&self, len: usize) -> String {
+        let parts: Vec<_> = self.parts[..len].iter().map(|(p, _)| p.as_str()).collect();
+        parts.join(".")
+    }
+}
+
+impl std::fmt::Display for UnresolvedIdent {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "UnresolvedIdent({}", &self.parts[0])?;
+        write!(f, "{}", &self.parts[0].0)?;
 
-        for p in &self.parts[1..] {
+        for (p, _) in &self.parts[1..] {
             write!(f, ".{}", p)?;
         }
 
-        write!(f, ")",)
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for UnresolvedIdent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "UnresolvedIdent({})", self)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UnresolvedImport {
+    pub path: UnresolvedIdent,
+    pub alias: Option<String>,
+    // Whether this is `using path.*`, binding all of the module's children rather than the path itself.
+    pub glob: bool,
+    // Names a glob leaves out, from `using path.* except a, b`.
+    pub except: Vec<(String, Range<usize>)>,
+    // A `pub using` re-exports the name, making it reachable from outside the importing module.
+    pub visibility: Visibility,
+    // From `using path if name;`, the import is only bound when the feature is enabled.
+    pub feature: Option<(String, Range<usize>)>,
+}
+
+impl UnresolvedImport {
+    // The name the import is bound under in the importing scope.
+    pub fn bound_name(&self) -> &str {
+        self.alias
+            .as_deref()
+            .unwrap_or(&self.path.parts.last().unwrap().0)
     }
 }
 
 #[derive(Debug)]
 pub enum UnresolvedAST {
-    Call { ident: UnresolvedIdent },
+    Call {
+        ident: UnresolvedIdent,
+        args: Vec<UnresolvedIdent>,
+        // Any further calls in `a.b().c()`, each looked up in the item the previous call resolved to.
+        chain: Vec<UnresolvedChainLink>,
+    },
+}
+
+#[derive(Debug)]
+pub struct UnresolvedChainLink {
+    pub ident: UnresolvedIdent,
+    pub args: Vec<UnresolvedIdent>,
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ResolvedIdent {
+    Item(ItemId),
+    // Stands in for an ident that failed to resolve, so we can keep going and report every failure.
+    Unresolved(UnresolvedIdent),
+}
+
+impl std::fmt::Debug for ResolvedIdent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResolvedIdent::Item(id) => write!(f, "{:?}", id),
+            ResolvedIdent::Unresolved(ident) => write!(f, "Unresolved({:?})", ident),
+        }
+    }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum ResolvedAST {
-    Call { ident: ItemId },
+    Call {
+        ident: ResolvedIdent,
+        args: Vec<ResolvedIdent>,
+        chain: Vec<ResolvedChainLink>,
+    },
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ResolvedChainLink {
+    pub ident: ResolvedIdent,
+    pub args: Vec<ResolvedIdent>,
+}
+
+// For analyses over the resolved bodies, driven by `Database::walk_resolved`. Calls that failed to
+// resolve aren't visited.
+pub trait ResolvedVisitor {
+    fn visit_call(&mut self, _caller: ItemId, _target: ItemId) {}
+}
+
+// An item and everything declared inside it, as parsed but not yet in a database. The parser
+// builds these, and `Database::load_items` declares them. The top level of a file is a tree of its
+// own, standing in for whichever module it's loaded into.
+#[derive(Debug)]
+pub struct ItemTree {
+    pub name: String,
+    pub span: Range<usize>,
+    pub kind: ItemKind,
+    pub visibility: Visibility,
+    pub skipped: bool,
+    pub entry: bool,
+    // Only set for a function with a complete, empty block.
+    pub empty: bool,
+    pub params: Vec<String>,
+    pub body: Vec<UnresolvedAST>,
+    pub alias_target: Option<UnresolvedIdent>,
+    pub imports: Vec<UnresolvedImport>,
+    pub children: Vec<ItemTree>,
+}
+
+impl ItemTree {
+    pub fn new(name: String, span: Range<usize>, kind: ItemKind, visibility: Visibility) -> Self {
+        Self {
+            name,
+            span,
+            kind,
+            visibility,
+            skipped: false,
+            entry: false,
+            empty: false,
+            params: Vec::new(),
+            body: Vec::new(),
+            alias_target: None,
+            imports: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    // The top level of a file. It keeps the root's name unless a `crate` wrapper renames it.
+    pub fn top_level() -> Self {
+        Self::new(
+            ROOT_NAME.to_owned(),
+            0..0,
+            ItemKind::Module,
+            Visibility::Public,
+        )
+    }
+
+    // Whether a `crate` wrapper gave the top level a name.
+    pub fn is_named_crate(&self) -> bool {
+        self.name != ROOT_NAME
+    }
 }
@@ -0,0 +1,215 @@
+use crate::{
+    ast::{UnresolvedAST, UnresolvedIdent, UnresolvedImport},
+    database::{Database, ItemId, ItemKind, Visibility},
+};
+
+// Builds a database directly rather than by parsing source. Nothing built this way has a location
+// in a source, so all the spans are empty.
+pub struct DatabaseBuilder {
+    database: Database,
+}
+
+impl Default for DatabaseBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DatabaseBuilder {
+    pub fn new() -> Self {
+        Self {
+            database: Database::new(),
+        }
+    }
+
+    // Everything is visible from the root, so there's no need for public top-level items.
+    pub fn module(mut self, name: &str, build: impl FnOnce(&mut ModuleBuilder)) -> Self {
+        let root = self.database.root();
+        build_module(&mut self.database, root, name, Visibility::Private, build);
+        self
+    }
+
+    pub fn function(mut self, name: &str, build: impl FnOnce(&mut FunctionBuilder)) -> Self {
+        let root = self.database.root();
+        build_function(&mut self.database, root, name, Visibility::Private, build);
+        self
+    }
+
+    pub fn import(mut self, path: &str) -> Self {
+        let root = self.database.root();
+        add_import(&mut self.database, root, path);
+        self
+    }
+
+    pub fn build(self) -> Database {
+        self.database
+    }
+}
+
+pub struct ModuleBuilder<'a> {
+    database: &'a mut Database,
+    module_id: ItemId,
+}
+
+impl ModuleBuilder<'_> {
+    pub fn module(&mut self, name: &str, build: impl FnOnce(&mut ModuleBuilder)) -> &mut Self {
+        build_module(
+            self.database,
+            self.module_id,
+            name,
+            Visibility::Private,
+            build,
+        );
+        self
+    }
+
+    pub fn pub_module(&mut self, name: &str, build: impl FnOnce(&mut ModuleBuilder)) -> &mut Self {
+        build_module(
+            self.database,
+            self.module_id,
+            name,
+            Visibility::Public,
+            build,
+        );
+        self
+    }
+
+    pub fn function(&mut self, name: &str, build: impl FnOnce(&mut FunctionBuilder)) -> &mut Self {
+        build_function(
+            self.database,
+            self.module_id,
+            name,
+            Visibility::Private,
+            build,
+        );
+        self
+    }
+
+    pub fn pub_function(
+        &mut self,
+        name: &str,
+        build: impl FnOnce(&mut FunctionBuilder),
+    ) -> &mut Self {
+        build_function(
+            self.database,
+            self.module_id,
+            name,
+            Visibility::Public,
+            build,
+        );
+        self
+    }
+
+    pub fn import(&mut self, path: &str) -> &mut Self {
+        add_import(self.database, self.module_id, path);
+        self
+    }
+}
+
+pub struct FunctionBuilder<'a> {
+    database: &'a mut Database,
+    func_id: ItemId,
+    body: Vec<UnresolvedAST>,
+}
+
+impl FunctionBuilder<'_> {
+    pub fn function(&mut self, name: &str, build: impl FnOnce(&mut FunctionBuilder)) -> &mut Self {
+        build_function(
+            self.database,
+            self.func_id,
+            name,
+            Visibility::Private,
+            build,
+        );
+        self
+    }
+
+    pub fn import(&mut self, path: &str) -> &mut Self {
+        add_import(self.database, self.func_id, path);
+        self
+    }
+
+    pub fn call(&mut self, path: &str, args: &[&str]) -> &mut Self {
+        self.body.push(UnresolvedAST::Call {
+            ident: ident(path),
+            args: args.iter().map(|a| ident(a)).collect(),
+            chain: Vec::new(),
+        });
+        self
+    }
+}
+
+fn build_module(
+    database: &mut Database,
+    parent_id: ItemId,
+    name: &str,
+    visibility: Visibility,
+    build: impl FnOnce(&mut ModuleBuilder),
+) {
+    let module_id = database.new_item(
+        name.to_owned(),
+        0..0,
+        ItemKind::Module,
+        visibility,
+        Some(parent_id),
+    );
+
+    build(&mut ModuleBuilder {
+        database,
+        module_id,
+    });
+}
+
+fn build_function(
+    database: &mut Database,
+    parent_id: ItemId,
+    name: &str,
+    visibility: Visibility,
+    build: impl FnOnce(&mut FunctionBuilder),
+) {
+    let func_id = database.new_item(
+        name.to_owned(),
+        0..0,
+        ItemKind::Function,
+        visibility,
+        Some(parent_id),
+    );
+
+    let mut builder = FunctionBuilder {
+        database,
+        func_id,
+        body: Vec::new(),
+    };
+    build(&mut builder);
+
+    let body = builder.body;
+    database.set_params(func_id, Vec::new());
+    database.set_unresolved_body(func_id, body);
+}
+
+fn add_import(database: &mut Database, item_id: ItemId, path: &str) {
+    // Same as the source syntax, a trailing `.*` makes it a glob.
+    let (path, glob) = match path.strip_suffix(".*") {
+        Some(path) => (path, true),
+        None => (path, false),
+    };
+
+    database.add_import(
+        item_id,
+        UnresolvedImport {
+            path: ident(path),
+            alias: None,
+            glob,
+            except: Vec::new(),
+            visibility: Visibility::Private,
+            feature: None,
+        },
+    );
+}
+
+fn ident(path: &str) -> UnresolvedIdent {
+    UnresolvedIdent {
+        parts: path.split('.').map(|p| (p.to_owned(), 0..0)).collect(),
+        span: 0..0,
+    }
+}
@@ -1,14 +1,66 @@
-use std::collections::BTreeMap;
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    ops::Range,
+};
 
-use crate::ast::{ResolvedAST, UnresolvedAST, UnresolvedIdent};
+use crate::{
+    ast::{
+        ItemTree, ResolvedAST, ResolvedChainLink, ResolvedIdent, ResolvedVisitor, UnresolvedAST,
+        UnresolvedChainLink, UnresolvedIdent, UnresolvedImport,
+    },
+    lexer,
+    line_index::LineIndex,
+    parser::{self, ParseError},
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum ItemKind {
+    Alias,
     Function,
     Module,
+    Value,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+// Lowercase, for messages. The Debug form is kept for dumps.
+impl std::fmt::Display for ItemKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ItemKind::Alias => "alias",
+            ItemKind::Function => "function",
+            ItemKind::Module => "module",
+            ItemKind::Value => "value",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChildOrder {
+    Alphabetical,
+    Source,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Visibility {
+    Public,
+    Private,
+}
+
+// Where an item came from. Only `Source` items were actually declared somewhere; the root holds the
+// top-level items, and `File` modules were created to hold files brought in with `add_file`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ItemOrigin {
+    Source,
+    Root,
+    File,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ItemId(usize);
 
 impl std::fmt::Debug for ItemId {
@@ -17,40 +69,457 @@ impl std::fmt::Debug for ItemId {
     }
 }
 
+fn is_path_keyword(part: &str) -> bool {
+    matches!(part, "crate" | "super" | "self")
+}
+
+// Every part of a path but the last is descended into, so should be a module.
+fn path_namespace(path: &UnresolvedIdent, index: usize, last: Namespace) -> Namespace {
+    if index + 1 < path.parts.len() {
+        Namespace::Type
+    } else {
+        last
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    // Levenshtein distance, keeping only the previous row of the table.
+    let b: Vec<_> = b.chars().collect();
+    let mut prev: Vec<_> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let substitute = prev[j] + usize::from(ca != cb);
+            row.push(substitute.min(prev[j + 1] + 1).min(row[j] + 1));
+        }
+        prev = row;
+    }
+
+    prev[b.len()]
+}
+
+fn suggestion(name: &str, mut candidates: Vec<&str>) -> String {
+    // Only close matches are worth suggesting, where how close depends on the length, so a short
+    // name isn't "corrected" to an unrelated one. A difference in case alone is the closest there
+    // is. Ties go to whichever comes first alphabetically, so the output is stable.
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates.sort_unstable();
+    let closest = candidates
+        .into_iter()
+        .filter(|&c| c != name)
+        .map(|c| {
+            let distance = if c.eq_ignore_ascii_case(name) {
+                0
+            } else {
+                edit_distance(c, name)
+            };
+            (distance, c)
+        })
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min();
+
+    match closest {
+        Some((_, c)) => format!("; did you mean `{}`?", c),
+        None => String::new(),
+    }
+}
+
+fn find_cycles_from(
+    node: usize,
+    deps: &[Vec<usize>],
+    chain: &mut Vec<usize>,
+    done: &mut [bool],
+    cycles: &mut Vec<Vec<usize>>,
+) {
+    if done[node] {
+        return;
+    }
+
+    // We keep the chain of nodes currently being followed, so running into one of them again
+    // means we've gone round in a circle.
+    if let Some(pos) = chain.iter().position(|&n| n == node) {
+        cycles.push(chain[pos..].to_vec());
+        return;
+    }
+
+    chain.push(node);
+    for &dep in &deps[node] {
+        find_cycles_from(dep, deps, chain, done, cycles);
+    }
+    chain.pop();
+
+    done[node] = true;
+}
+
+// State for finding strongly connected components with Tarjan's algorithm. A node's index is `None`
+// until it's been visited.
+struct SccState {
+    next_index: usize,
+    index: Vec<Option<usize>>,
+    low: Vec<usize>,
+    stack: Vec<usize>,
+    on_stack: Vec<bool>,
+    components: Vec<Vec<usize>>,
+}
+
+impl SccState {
+    fn new(len: usize) -> Self {
+        Self {
+            next_index: 0,
+            index: vec![None; len],
+            low: vec![0; len],
+            stack: Vec::new(),
+            on_stack: vec![false; len],
+            components: Vec::new(),
+        }
+    }
+}
+
+fn strong_connect(node: usize, deps: &[Vec<usize>], state: &mut SccState) {
+    state.index[node] = Some(state.next_index);
+    state.low[node] = state.next_index;
+    state.next_index += 1;
+    state.stack.push(node);
+    state.on_stack[node] = true;
+
+    for &dep in &deps[node] {
+        match state.index[dep] {
+            None => {
+                strong_connect(dep, deps, state);
+                state.low[node] = state.low[node].min(state.low[dep]);
+            }
+            Some(dep_index) if state.on_stack[dep] => {
+                state.low[node] = state.low[node].min(dep_index);
+            }
+            Some(_) => {}
+        }
+    }
+
+    // Nothing below this node reached back above it, so everything still on the stack above it
+    // is one component.
+    if state.index[node] == Some(state.low[node]) {
+        let mut component = Vec::new();
+        while let Some(member) = state.stack.pop() {
+            state.on_stack[member] = false;
+            component.push(member);
+            if member == node {
+                break;
+            }
+        }
+        state.components.push(component);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    // The item whose body or scope contained the problem.
+    pub item: ItemId,
+    pub severity: Severity,
+    // Where in the source the problem is. Problems with a declaration point at its name.
+    pub span: Range<usize>,
+}
+
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ItemHeader {
     kind: ItemKind,
     name: String,
     parent: ItemId,
     id: ItemId,
+    visibility: Visibility,
+    origin: ItemOrigin,
+    // The name in the declaration. Items that weren't parsed, like the root or those made with the
+    // builder, have an empty span at the start.
+    span: Range<usize>,
+    // Removed items keep their header so ids stay stable, but can't be reached any more.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    removed: bool,
+    // Marked `#[skip]`: declared, but can't be named and isn't resolved.
+    skipped: bool,
+}
+
+impl ItemHeader {
+    pub fn id(&self) -> ItemId {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn kind(&self) -> ItemKind {
+        self.kind
+    }
+
+    pub fn parent(&self) -> ItemId {
+        self.parent
+    }
+
+    pub fn visibility(&self) -> Visibility {
+        self.visibility
+    }
+}
+
+// A read-only view of the resolved state of a `Database`, for analyses that want to hold on to it.
+// Everything is borrowed, nothing is copied.
+pub struct ResolvedView<'a> {
+    database: &'a Database,
+}
+
+impl<'a> ResolvedView<'a> {
+    // Removed and skipped items aren't included.
+    pub fn headers(&self) -> impl Iterator<Item = &'a ItemHeader> {
+        self.database.live_headers()
+    }
+
+    pub fn resolved_body(&self, id: ItemId) -> &'a [ResolvedAST] {
+        self.database.resolved_bodies.get(&id).map_or(&[], |b| b)
+    }
+
+    pub fn resolved_bodies(&self) -> impl Iterator<Item = (ItemId, &'a [ResolvedAST])> {
+        let bodies = &self.database.resolved_bodies;
+        bodies.iter().map(|(&id, body)| (id, body.as_slice()))
+    }
+
+    // Imports included, in no particular order. `Database::children_of` sorts them.
+    pub fn children(&self, id: ItemId) -> impl Iterator<Item = (&'a str, ItemId)> {
+        let children = &self.database.get_scope(id).children;
+        children.iter().map(|(name, &child)| (name.as_str(), child))
+    }
+}
+
+// A borrowed view of the resolved state of a `Database`, for serializing.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+pub struct DatabaseSnapshot<'a> {
+    // Removed items are left out.
+    pub headers: Vec<&'a ItemHeader>,
+    pub resolved_bodies: &'a BTreeMap<ItemId, Vec<ResolvedAST>>,
+}
+
+// A diagnostic as editors want it, with the item's path and line/column positions rather than ids
+// and byte offsets.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct JsonDiagnostic<'a> {
+    message: &'a str,
+    severity: String,
+    item: String,
+    start: JsonPosition,
+    end: JsonPosition,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct JsonPosition {
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+// Modules are kept apart from everything else, so a module and a function can share a name. Path
+// descent looks for a module first, and anything else looks for the other kind first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Namespace {
+    Type,
+    Value,
+}
+
+impl Namespace {
+    fn of(kind: ItemKind) -> Self {
+        match kind {
+            ItemKind::Module => Namespace::Type,
+            ItemKind::Alias | ItemKind::Function | ItemKind::Value => Namespace::Value,
+        }
+    }
+
+    fn other(self) -> Self {
+        match self {
+            Namespace::Type => Namespace::Value,
+            Namespace::Value => Namespace::Type,
+        }
+    }
+}
+
+// The names bound in a scope, one map per namespace.
+struct Children {
+    types: HashMap<String, ItemId>,
+    values: HashMap<String, ItemId>,
+}
+
+impl Children {
+    fn new() -> Self {
+        Self {
+            types: HashMap::new(),
+            values: HashMap::new(),
+        }
+    }
+
+    fn map(&self, namespace: Namespace) -> &HashMap<String, ItemId> {
+        match namespace {
+            Namespace::Type => &self.types,
+            Namespace::Value => &self.values,
+        }
+    }
+
+    fn get(&self, name: &str, namespace: Namespace) -> Option<ItemId> {
+        self.map(namespace).get(name).copied()
+    }
+
+    // Falls back to the other namespace, so a name only bound in one is always found.
+    fn get_preferring(&self, name: &str, namespace: Namespace) -> Option<ItemId> {
+        self.get(name, namespace)
+            .or_else(|| self.get(name, namespace.other()))
+    }
+
+    fn contains_key(&self, name: &str) -> bool {
+        self.types.contains_key(name) || self.values.contains_key(name)
+    }
+
+    fn insert(&mut self, name: String, id: ItemId, namespace: Namespace) {
+        match namespace {
+            Namespace::Type => self.types.insert(name, id),
+            Namespace::Value => self.values.insert(name, id),
+        };
+    }
+
+    // Only removes the name if it's bound to the given item.
+    fn remove(&mut self, name: &str, id: ItemId) {
+        for map in [&mut self.types, &mut self.values] {
+            if map.get(name) == Some(&id) {
+                map.remove(name);
+            }
+        }
+    }
+
+    fn retain(&mut self, mut f: impl FnMut(&String, &ItemId) -> bool) {
+        self.types.retain(|name, id| f(name, id));
+        self.values.retain(|name, id| f(name, id));
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&String, &ItemId)> {
+        self.types.iter().chain(&self.values)
+    }
 }
 
 pub struct Scope {
-    unresolved_imports: Vec<UnresolvedIdent>,
-    children: BTreeMap<String, ItemId>,
+    unresolved_imports: Vec<UnresolvedImport>,
+    // Lookups are the hot path, so this isn't ordered. Anything that shows children to the user
+    // sorts them first.
+    children: Children,
+    // Names bound by `pub using`, which can be reached from outside like public items.
+    reexports: HashSet<String>,
+    // Every distinct item a glob offered for each name the globs bound. More than one means a
+    // bare use of the name is ambiguous.
+    glob_candidates: HashMap<String, Vec<ItemId>>,
+    // The items declared in this scope, in the order they were declared.
+    declaration_order: Vec<ItemId>,
 }
 
 impl Scope {
     fn new() -> Self {
         Self {
             unresolved_imports: Vec::new(),
-            children: BTreeMap::new(),
+            children: Children::new(),
+            reexports: HashSet::new(),
+            glob_candidates: HashMap::new(),
+            declaration_order: Vec::new(),
         }
     }
 
-    fn add_child(&mut self, name: String, id: ItemId) {
-        // Might want to handle name collision here.
-        self.children.insert(name, id);
+    fn add_child(&mut self, name: String, id: ItemId, namespace: Namespace) {
+        // Collisions between declared items are caught in `Database::new_item`, so this is only
+        // reached unchecked when binding imports.
+        self.children.insert(name, id, namespace);
+    }
+}
+
+// Why resolving a path failed, along with how far it got: how many parts resolved, and what the
+// last of those resolved to.
+struct ResolveError {
+    diagnostic: Diagnostic,
+    matched: usize,
+    last_good: Option<ItemId>,
+}
+
+impl ResolveError {
+    fn new(matched: usize, last_good: Option<ItemId>, diagnostic: Diagnostic) -> Self {
+        Self {
+            diagnostic,
+            matched,
+            last_good,
+        }
     }
 }
 
+// What the root is called until a `crate` wrapper names it.
+pub(crate) const ROOT_NAME: &str = "<ROOT>";
+
+type VisibleSymbolCache = HashMap<(ItemId, String, Namespace), Option<ItemId>>;
+
 pub struct Database {
     headers: Vec<ItemHeader>,
     root: ItemId,
     // BTreeMap is just so the printing has the same order each time.
     unresolved_bodies: BTreeMap<ItemId, Vec<UnresolvedAST>>,
     resolved_bodies: BTreeMap<ItemId, Vec<ResolvedAST>>,
+    params: BTreeMap<ItemId, Vec<String>>,
+    unresolved_aliases: BTreeMap<ItemId, UnresolvedIdent>,
+    resolved_aliases: BTreeMap<ItemId, ItemId>,
     scopes: Vec<Scope>,
+    // Problems found while declaring items, reported along with the resolution diagnostics. Each
+    // is kept with the items it's about, so it can be dropped if one of them is removed.
+    declaration_diagnostics: Vec<(Diagnostic, Vec<ItemId>)>,
+    diagnostics: Vec<Diagnostic>,
+    // Only active while resolving bodies, as imports change what's visible.
+    visible_symbol_cache: RefCell<Option<VisibleSymbolCache>>,
+    // Only ever turned off by tests, to check the cache doesn't change any answers.
+    use_symbol_cache: bool,
+    // The steps lookups went through, only recorded while tracing is on.
+    trace: RefCell<Option<Vec<String>>>,
+    // When set, an item can't name itself, and has to use `self` instead.
+    strict_self: bool,
+    // When set, the top-level items can be named from anywhere without being imported.
+    implicit_root_visibility: bool,
+    // Functions whose block was parsed in full and had nothing in it.
+    empty_functions: BTreeSet<ItemId>,
+    warn_empty: bool,
+    // The functions marked `entry`, in declaration order. Only the first is the entry point, where
+    // reachability starts from, and the rest are reported.
+    entries: Vec<ItemId>,
+    // Features that `using ... if name;` imports can be guarded by.
+    features: HashSet<String>,
+    // The names that imports failed to bind, by scope, along with the import's path. Uses of them
+    // point back to the import rather than just saying the name is missing.
+    failed_imports: HashMap<(ItemId, String), String>,
+    // Top-level items merged in from another database, mapped to the module they were grafted
+    // under. That module stands in for the root for anything inside them, so `crate` paths and
+    // top-level lookups keep meaning what they did in the other database.
+    merged_roots: HashMap<ItemId, ItemId>,
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Database {
@@ -60,15 +529,69 @@ impl Database {
             root: ItemId(0),
             unresolved_bodies: BTreeMap::new(),
             resolved_bodies: BTreeMap::new(),
+            params: BTreeMap::new(),
+            unresolved_aliases: BTreeMap::new(),
+            resolved_aliases: BTreeMap::new(),
             scopes: Vec::new(),
+            declaration_diagnostics: Vec::new(),
+            diagnostics: Vec::new(),
+            visible_symbol_cache: RefCell::new(None),
+            use_symbol_cache: true,
+            trace: RefCell::new(None),
+            strict_self: false,
+            implicit_root_visibility: true,
+            empty_functions: BTreeSet::new(),
+            warn_empty: false,
+            entries: Vec::new(),
+            features: HashSet::new(),
+            failed_imports: HashMap::new(),
+            merged_roots: HashMap::new(),
         };
 
-        s.new_item("<ROOT>".to_owned(), ItemKind::Module, None);
+        let root = s.new_item(
+            ROOT_NAME.to_owned(),
+            0..0,
+            ItemKind::Module,
+            Visibility::Public,
+            None,
+        );
+        s.headers[root.0].origin = ItemOrigin::Root;
 
         s
     }
 
-    pub fn new_item(&mut self, name: String, kind: ItemKind, parent: Option<ItemId>) -> ItemId {
+    pub fn root(&self) -> ItemId {
+        self.root
+    }
+
+    // Gives the root the name from a `crate Name { ... }` wrapper. There can only be one.
+    pub fn set_crate_name(&mut self, name: String, span: Range<usize>) -> Result<(), Diagnostic> {
+        let root = &mut self.headers[self.root.0];
+        if root.name != ROOT_NAME {
+            return Err(Diagnostic {
+                message: format!(
+                    "crate `{}` is already named, it can't also be `{}`",
+                    root.name, name
+                ),
+                item: self.root,
+                severity: Severity::Error,
+                span,
+            });
+        }
+
+        root.name = name;
+        root.span = span;
+        Ok(())
+    }
+
+    pub fn new_item(
+        &mut self,
+        name: String,
+        span: Range<usize>,
+        kind: ItemKind,
+        visibility: Visibility,
+        parent: Option<ItemId>,
+    ) -> ItemId {
         let id = ItemId(self.headers.len());
         let parent = parent.unwrap_or(self.root);
 
@@ -77,166 +600,2440 @@ impl Database {
             name: name.clone(),
             parent,
             id,
+            visibility,
+            origin: ItemOrigin::Source,
+            span: span.clone(),
+            removed: false,
+            skipped: false,
         });
 
         self.scopes.push(Scope::new());
-        self.scopes[parent.0].add_child(name, id);
+
+        // The root is its own parent, but shouldn't be listed as its own child.
+        if id == parent {
+            return id;
+        }
+
+        // The first declaration wins, the later one still gets an item but can't be named. Only
+        // items in the same namespace clash.
+        let namespace = Namespace::of(kind);
+        if let Some(existing) = self.get_scope(parent).children.get(&name, namespace) {
+            self.report_duplicate(existing, id);
+        } else {
+            self.scopes[parent.0].add_child(name, id, namespace);
+        }
+        self.scopes[parent.0].declaration_order.push(id);
 
         id
     }
 
-    fn get_header(&self, item_id: ItemId) -> &ItemHeader {
-        &self.headers[item_id.0]
+    fn report_duplicate(&mut self, existing: ItemId, id: ItemId) {
+        let header = self.get_header(id);
+        let diagnostic = Diagnostic {
+            message: format!(
+                "`{}` is declared more than once: {:?} and {:?}",
+                header.name, existing, id
+            ),
+            item: header.parent,
+            severity: Severity::Error,
+            span: header.span.clone(),
+        };
+        self.declaration_diagnostics
+            .push((diagnostic, vec![existing, id]));
     }
 
-    pub fn set_unresolved_body(&mut self, id: ItemId, body: Vec<UnresolvedAST>) {
-        self.unresolved_bodies.insert(id, body);
-    }
+    // Declares what's in the tree under `parent` as if it had been parsed there, so duplicates and
+    // extra entry points are reported the same way. The tree is the top level of a file, like the
+    // one from `parse_to_items`, so only its imports and children are declared. A `crate` name
+    // only applies when loading into the root.
+    pub fn load_items(&mut self, parent: ItemId, tree: ItemTree) {
+        if parent == self.root && tree.is_named_crate() {
+            if let Err(diagnostic) = self.set_crate_name(tree.name, tree.span) {
+                self.declaration_diagnostics.push((diagnostic, Vec::new()));
+            }
+        }
+        for import in tree.imports {
+            self.add_import(parent, import);
+        }
 
-    pub fn get_unresolved_body(&self, id: ItemId) -> &[UnresolvedAST] {
-        &self.unresolved_bodies[&id]
+        self.load_children(parent, tree.children);
     }
 
-    pub fn set_resolved_body(&mut self, id: ItemId, body: Vec<ResolvedAST>) {
-        self.resolved_bodies.insert(id, body);
+    fn load_children(&mut self, parent: ItemId, items: Vec<ItemTree>) {
+        for item in items {
+            let id = self.new_item(
+                item.name,
+                item.span,
+                item.kind,
+                item.visibility,
+                Some(parent),
+            );
+
+            if item.kind == ItemKind::Function {
+                self.set_params(id, item.params);
+                self.set_unresolved_body(id, item.body);
+            }
+            if let Some(target) = item.alias_target {
+                self.set_alias_target(id, target);
+            }
+            for import in item.imports {
+                self.add_import(id, import);
+            }
+            if item.empty {
+                self.mark_empty_function(id);
+            }
+            if item.entry {
+                self.set_entry(id);
+            }
+
+            self.load_children(id, item.children);
+
+            // Skipped last, so the children are still declared under it.
+            if item.skipped {
+                self.skip_item(id);
+            }
+        }
     }
 
-    fn get_scope(&self, id: ItemId) -> &Scope {
-        &self.scopes[id.0]
+    // Grafts `other`'s items, and its top-level imports, beneath `under` as if they'd been declared
+    // there. Everything gets a new id, and bodies come across unresolved, so `resolve_idents` has
+    // to be run again afterwards. Names clashing with ones already in `under` are reported like
+    // any other duplicate declaration. Inside the merged items, `crate` paths and top-level lookups
+    // start from `under`, so they find the same items they did in `other`. Its top-level imports
+    // are resolved from `under` itself though, where `crate` still means this root.
+    // Only the items and imports come across: `other`'s crate name, enabled features and settings,
+    // like `set_strict_self`, are discarded.
+    pub fn merge(&mut self, mut other: Database, under: ItemId) {
+        let items = other.take_items();
+        let first_new = self.headers.len();
+        self.load_items(under, items);
+
+        let grafted = self.headers[first_new..]
+            .iter()
+            .filter(|h| h.parent == under)
+            .map(|h| (h.id, under))
+            .collect::<Vec<_>>();
+        self.merged_roots.extend(grafted);
     }
 
-    pub fn add_import(&mut self, id: ItemId, ident: UnresolvedIdent) {
-        self.scopes[id.0].unresolved_imports.push(ident);
+    // The module that `crate` refers to from this item, and whose children are visible everywhere
+    // in it: the root, unless the item was merged in from another database.
+    fn crate_root(&self, item_id: ItemId) -> ItemId {
+        let mut current = item_id;
+        loop {
+            if let Some(&root) = self.merged_roots.get(&current) {
+                return root;
+            }
+            if current == self.root {
+                return self.root;
+            }
+            current = self.get_header(current).parent;
+        }
     }
 
-    pub fn resolve_idents(&mut self) {
-        // The first thing we do is resolve idents on the scopes. This is because resolution of item bodies
-        // will look at it's parent module's scope for symbols.
-        let item_ids: Vec<_> = self.headers.iter().map(|h| h.id).collect();
+    // Moves the root's imports and items back out as a tree. The crate name is left behind.
+    fn take_items(&mut self) -> ItemTree {
+        let root = self.root;
+        let mut tree = ItemTree::top_level();
+        tree.imports = std::mem::take(&mut self.scopes[root.0].unresolved_imports);
+        tree.children = self.take_children(root);
+        tree
+    }
 
-        for &item_id in &item_ids {
-            let imports = self.get_scope(item_id).unresolved_imports.clone();
+    fn take_children(&mut self, parent: ItemId) -> Vec<ItemTree> {
+        let children = std::mem::take(&mut self.scopes[parent.0].declaration_order);
+        children
+            .into_iter()
+            .map(|id| {
+                let header = &self.headers[id.0];
+                ItemTree {
+                    name: header.name.clone(),
+                    span: header.span.clone(),
+                    kind: header.kind,
+                    visibility: header.visibility,
+                    skipped: header.skipped,
+                    entry: self.entries.contains(&id),
+                    empty: self.empty_functions.contains(&id),
+                    params: self.params.remove(&id).unwrap_or_default(),
+                    body: self.unresolved_bodies.remove(&id).unwrap_or_default(),
+                    alias_target: self.unresolved_aliases.remove(&id),
+                    imports: std::mem::take(&mut self.scopes[id.0].unresolved_imports),
+                    children: self.take_children(id),
+                }
+            })
+            .collect()
+    }
 
-            for import in imports {
-                let name = import.parts.last().unwrap().clone();
-                let resolved_id = self.resolve_single_ident(item_id, &import);
+    // Anything referring to the item by id, like resolved bodies, is unaffected. Paths naming it by
+    // its old name will fail the next time they're resolved.
+    pub fn rename_item(&mut self, id: ItemId, new_name: String) -> Result<(), Diagnostic> {
+        let header = self.get_header(id);
+        let parent = header.parent;
+        let namespace = Namespace::of(header.kind);
 
-                self.scopes[item_id.0].add_child(name, resolved_id);
-            }
+        if !lexer::is_valid_ident(&new_name) {
+            return Err(Diagnostic {
+                message: format!(
+                    "cannot rename `{}` to `{}`: it is not a valid identifier",
+                    self.full_path(id),
+                    new_name
+                ),
+                item: parent,
+                severity: Severity::Error,
+                span: header.span.clone(),
+            });
         }
 
-        // Now we iterate over the function bodies, and resolve idents within those.
-        for item_id in item_ids {
-            if self.get_header(item_id).kind != ItemKind::Function {
-                continue;
+        if let Some(existing) = self.get_scope(parent).children.get(&new_name, namespace) {
+            if existing != id {
+                return Err(Diagnostic {
+                    message: format!(
+                        "cannot rename `{}` to `{}`: the name is already used by `{}`",
+                        self.full_path(id),
+                        new_name,
+                        self.full_path(existing)
+                    ),
+                    item: parent,
+                    severity: Severity::Error,
+                    span: header.span.clone(),
+                });
             }
+        }
 
-            let body = self.get_unresolved_body(item_id);
-            let new_body = self.resolve_idents_in_body(item_id, body);
-            self.set_resolved_body(item_id, new_body);
+        // A duplicate declaration never got an entry, so there's nothing to move for it.
+        let old_name = std::mem::replace(&mut self.headers[id.0].name, new_name.clone());
+        let scope = &mut self.scopes[parent.0];
+        if scope.children.get(&old_name, namespace) == Some(id) {
+            scope.children.remove(&old_name, id);
+            scope.add_child(new_name, id, namespace);
         }
-    }
 
-    fn resolve_idents_in_body(
-        &self,
-        current_func: ItemId,
-        body: &[UnresolvedAST],
-    ) -> Vec<ResolvedAST> {
-        let mut new_body = Vec::new();
+        Ok(())
+    }
 
-        for node in body {
-            match node {
-                UnresolvedAST::Call { ident } => {
-                    let resolved_ident = self.resolve_single_ident(current_func, ident);
-                    new_body.push(ResolvedAST::Call {
-                        ident: resolved_ident,
-                    });
-                }
-            }
+    // Removes the item along with everything declared inside it. Returns the functions that called
+    // any of the removed items; those calls are left unresolved until the next resolve.
+    pub fn remove_item(&mut self, id: ItemId) -> Vec<ItemId> {
+        let mut removed = vec![id];
+        let mut i = 0;
+        while let Some(&current) = removed.get(i) {
+            removed.extend(&self.get_scope(current).declaration_order);
+            i += 1;
         }
 
-        new_body
-    }
+        let parent = self.get_header(id).parent;
+        self.scopes[parent.0]
+            .declaration_order
+            .retain(|&child| child != id);
 
-    fn resolve_single_ident(&self, item_id: ItemId, ident: &UnresolvedIdent) -> ItemId {
-        // The first part of the ident (e.g. "A2" in "A2.a_func") is where we start traversing *down*
-        // into the module tree.
+        for &current in &removed {
+            self.headers[current.0].removed = true;
+            self.scopes[current.0] = Scope::new();
+            self.unresolved_bodies.remove(&current);
+            self.resolved_bodies.remove(&current);
+            self.params.remove(&current);
+            self.unresolved_aliases.remove(&current);
+            self.resolved_aliases.remove(&current);
+            self.empty_functions.remove(&current);
+            self.merged_roots.remove(&current);
+        }
+        self.entries.retain(|e| !removed.contains(e));
+        self.failed_imports
+            .retain(|(scope, _), _| !removed.contains(scope));
+        self.declaration_diagnostics
+            .retain(|(_, about)| !about.iter().any(|a| removed.contains(a)));
 
-        // But first, we need to find out what item the first part refers to. To do that we need to
-        // traverse *up* the module tree, starting from the current item, looking for a matching ID.
-        // The current item here would be, for example, a function that we're resolving the body for.
-        // This would be where you would plug in something like Rust's "crate", "super" or "self" path
-        // segments.
-        let root = self.get_visible_symbol(item_id, &ident.parts[0]);
+        // Imports elsewhere may have bound the removed items too, not just their parents.
+        let headers = &self.headers;
+        for scope in &mut self.scopes {
+            scope.children.retain(|_, child| !headers[child.0].removed);
+        }
 
-        // Now that we know what the root is, we can start traversing down the tree into its children.
-        let mut current_item = root;
-        for sub_ident in &ident.parts[1..] {
-            let current_header = self.get_header(current_item);
-            if current_header.kind != ItemKind::Module {
-                panic!("Cannot resolve into non-modules {}", sub_ident);
+        // If it was the first of several declarations with its name, the next one takes its place.
+        let header = self.get_header(id);
+        let (name, namespace) = (header.name.clone(), Namespace::of(header.kind));
+        if self
+            .get_scope(parent)
+            .children
+            .get(&name, namespace)
+            .is_none()
+        {
+            let duplicates: Vec<_> = (self.get_scope(parent).declaration_order.iter())
+                .copied()
+                .filter(|&d| {
+                    let header = self.get_header(d);
+                    header.name == name
+                        && Namespace::of(header.kind) == namespace
+                        && !header.skipped
+                })
+                .collect();
+            if let Some((&first, rest)) = duplicates.split_first() {
+                self.scopes[parent.0].add_child(name, first, namespace);
+                for &duplicate in rest {
+                    self.report_duplicate(first, duplicate);
+                }
             }
+        }
 
-            let cur_scope = self.get_scope(current_item);
-            let child_id = *cur_scope.children.get(sub_ident).unwrap();
+        let mut callers: Vec<_> = removed
+            .iter()
+            .flat_map(|&current| self.references_to(current))
+            .collect();
+        callers.sort_unstable();
+        callers.dedup();
+
+        // Anything still pointing at a removed item goes back to how it was written, so it's never
+        // followed to a header that's gone.
+        for &caller in &callers {
+            let (Some(resolved), Some(unresolved)) = (
+                self.resolved_bodies.get_mut(&caller),
+                self.unresolved_bodies.get(&caller),
+            ) else {
+                continue;
+            };
+            for (resolved, unresolved) in resolved.iter_mut().zip(unresolved) {
+                let ResolvedAST::Call { ident, args, chain } = resolved;
+                let UnresolvedAST::Call {
+                    ident: old_ident,
+                    args: old_args,
+                    chain: old_chain,
+                } = unresolved;
 
-            current_item = child_id;
+                unresolve_removed(&self.headers, ident, old_ident);
+                for (arg, old_arg) in args.iter_mut().zip(old_args) {
+                    unresolve_removed(&self.headers, arg, old_arg);
+                }
+                for (link, old_link) in chain.iter_mut().zip(old_chain) {
+                    unresolve_removed(&self.headers, &mut link.ident, &old_link.ident);
+                    for (arg, old_arg) in link.args.iter_mut().zip(&old_link.args) {
+                        unresolve_removed(&self.headers, arg, old_arg);
+                    }
+                }
+            }
         }
+        let headers = &self.headers;
+        self.resolved_aliases
+            .retain(|_, target| !headers[target.0].removed);
 
-        // Once we've got through the sub-idents, we're done.
-        current_item
+        callers
     }
 
-    fn get_visible_symbol(&self, item_id: ItemId, name: &str) -> ItemId {
-        // First, we check ourselves. It's valid for an item to refer to itself, so that should
-        // come first.
-        let own_header = self.get_header(item_id);
-        if name == own_header.name {
-            return item_id;
-        }
+    // The item stays in the tree, but its parent's scope no longer binds it, so nothing can refer
+    // to it. Neither it nor anything inside it is resolved.
+    pub fn skip_item(&mut self, id: ItemId) {
+        let header = &mut self.headers[id.0];
+        header.skipped = true;
 
-        // Now we check our children.
-        let own_scope = self.get_scope(item_id);
-        if let Some(child_id) = own_scope.children.get(name) {
-            return *child_id;
-        }
+        self.scopes[header.parent.0]
+            .children
+            .remove(&header.name, id);
+    }
 
-        // If we are not a module, we then check out parent module's children.
-        // The reason we don't traverse up if we're a module, or traverse upward
-        // past our parent module is so that we only see symbols imported into
-        // *our* module.
-        if own_header.kind != ItemKind::Module {
-            // In this, we don't allow nested functions, so a function's parent is known
-            // to be a module. If you do allow them, then you may want to repeat this logic
-            // in each scope until you get to a module.
-            let parent_scope = self.get_scope(own_header.parent);
-            if let Some(child) = parent_scope.children.get(name) {
-                return *child;
+    pub fn is_skipped(&self, id: ItemId) -> bool {
+        let mut current = id;
+        loop {
+            let header = self.get_header(current);
+            if header.skipped {
+                return true;
+            }
+            if current == self.root {
+                return false;
             }
+            current = header.parent;
         }
+    }
 
-        // If we still haven't found a symbol, we check the root.
-        // In the example file, the roots would be A1 and B1.
-        let root_scope = self.get_scope(self.root);
-        if let Some(child) = root_scope.children.get(name) {
-            return *child;
+    fn live_headers(&self) -> impl Iterator<Item = &ItemHeader> {
+        self.headers
+            .iter()
+            .filter(|h| !h.removed && !self.is_skipped(h.id))
+    }
+
+    fn get_header(&self, item_id: ItemId) -> &ItemHeader {
+        &self.headers[item_id.0]
+    }
+
+    pub fn add_file(&mut self, module_path: &[&str], source: &str) -> Result<(), Vec<ParseError>> {
+        let tokens = lexer::lex(source).map_err(|e| vec![e.into()])?;
+
+        // Files can share parts of their module path, so only create the modules that don't
+        // exist yet. A file has nowhere to mark its own module as `pub`, so they're all public.
+        let mut module_id = self.root;
+        for &name in module_path {
+            module_id = match self.get_module_child(module_id, name, Namespace::Type) {
+                Some(child_id) if self.get_header(child_id).kind == ItemKind::Module => child_id,
+                _ => {
+                    let new_id = self.new_item(
+                        name.to_owned(),
+                        0..0,
+                        ItemKind::Module,
+                        Visibility::Public,
+                        Some(module_id),
+                    );
+                    self.headers[new_id.0].origin = ItemOrigin::File;
+                    new_id
+                }
+            };
         }
 
-        panic!("symbol not found");
+        parser::parse_into_module(self, &tokens, module_id)
     }
 
-    pub fn print_headers(&self) {
-        eprintln!(" == Headers ==");
-        eprintln!("{:#?}\n\n", self.headers);
+    pub fn name_of(&self, id: ItemId) -> &str {
+        &self.get_header(id).name
     }
 
-    pub fn print_unresolved_ast(&self) {
-        eprintln!(" == Unresolved ASTs ==");
-        eprintln!("{:#?}\n\n", self.unresolved_bodies);
+    pub fn kind_of(&self, id: ItemId) -> ItemKind {
+        self.get_header(id).kind
     }
 
-    pub fn print_resolved_ast(&self) {
-        eprintln!(" == Resolved ASTs ==");
+    pub fn origin_of(&self, id: ItemId) -> ItemOrigin {
+        self.get_header(id).origin
+    }
+
+    // The root is its own parent.
+    pub fn parent_of(&self, id: ItemId) -> ItemId {
+        self.get_header(id).parent
+    }
+
+    pub fn full_path(&self, id: ItemId) -> String {
+        // Walk up to the root collecting names, skipping the root itself as it isn't a real item.
+        let mut parts = Vec::new();
+        let mut current = id;
+        while current != self.root {
+            let header = self.get_header(current);
+            parts.push(header.name.as_str());
+            current = header.parent;
+        }
+
+        parts.reverse();
+        parts.join(".")
+    }
+
+    // For messages. The root's path is empty, so it's shown by name instead.
+    pub fn display_path(&self, id: ItemId) -> String {
+        if id == self.root {
+            self.get_header(id).name.clone()
+        } else {
+            self.full_path(id)
+        }
+    }
+
+    pub fn set_params(&mut self, id: ItemId, params: Vec<String>) {
+        self.params.insert(id, params);
+    }
+
+    pub fn params_of(&self, id: ItemId) -> &[String] {
+        self.params.get(&id).map_or(&[], |p| p)
+    }
+
+    pub fn set_alias_target(&mut self, id: ItemId, target: UnresolvedIdent) {
+        self.unresolved_aliases.insert(id, target);
+    }
+
+    pub fn mark_empty_function(&mut self, id: ItemId) {
+        self.empty_functions.insert(id);
+    }
+
+    // Only true for a function that was parsed with a complete, empty block. A function whose body
+    // failed to parse may have no statements without being empty.
+    pub fn is_empty_function(&self, id: ItemId) -> bool {
+        self.empty_functions.contains(&id)
+    }
+
+    // Only one function can be the entry point. Any after the first are reported when resolving,
+    // and ignored.
+    pub fn set_entry(&mut self, id: ItemId) {
+        if !self.entries.contains(&id) {
+            self.entries.push(id);
+        }
+    }
+
+    pub fn entry(&self) -> Option<ItemId> {
+        self.entries.first().copied()
+    }
+
+    fn extra_entry_diagnostics(&self) -> impl Iterator<Item = Diagnostic> + '_ {
+        let first = self.entry();
+        self.entries.iter().skip(1).map(move |&id| Diagnostic {
+            message: format!(
+                "more than one entry point: `{}` and `{}`",
+                self.full_path(first.unwrap()),
+                self.full_path(id)
+            ),
+            item: id,
+            severity: Severity::Error,
+            span: self.get_header(id).span.clone(),
+        })
+    }
+
+    pub fn set_unresolved_body(&mut self, id: ItemId, body: Vec<UnresolvedAST>) {
+        self.unresolved_bodies.insert(id, body);
+    }
+
+    pub fn get_unresolved_body(&self, id: ItemId) -> &[UnresolvedAST] {
+        // A function whose header failed to parse won't have a body.
+        self.unresolved_bodies.get(&id).map_or(&[], |b| b)
+    }
+
+    pub fn set_resolved_body(&mut self, id: ItemId, body: Vec<ResolvedAST>) {
+        self.resolved_bodies.insert(id, body);
+    }
+
+    fn get_scope(&self, id: ItemId) -> &Scope {
+        &self.scopes[id.0]
+    }
+
+    pub fn add_import(&mut self, id: ItemId, import: UnresolvedImport) {
+        self.scopes[id.0].unresolved_imports.push(import);
+    }
+
+    // The imports as written, whether or not they've been resolved. Each one's path is the
+    // `UnresolvedIdent`, alongside the alias and other details.
+    pub fn pending_imports(&self, id: ItemId) -> &[UnresolvedImport] {
+        &self.get_scope(id).unresolved_imports
+    }
+
+    // Safe to call more than once. Everything a previous call bound is dropped first, so each
+    // call resolves from scratch and gives the same result.
+    pub fn resolve_idents(&mut self) -> Vec<Diagnostic> {
+        self.clear_import_bindings();
+        let mut diagnostics: Vec<_> = self
+            .declaration_diagnostics
+            .iter()
+            .map(|(diagnostic, _)| diagnostic.clone())
+            .chain(self.extra_entry_diagnostics())
+            .collect();
+
+        // The first thing we do is resolve idents on the scopes. This is because resolution of item bodies
+        // will look at it's parent module's scope for symbols.
+        let item_ids: Vec<_> = self.live_headers().map(|h| h.id).collect();
+        self.resolve_imports(&item_ids, &mut diagnostics);
+
+        // Now we iterate over the function bodies, and resolve idents within those.
+        // The scopes don't change from here on, so the same lookups will give the same answers.
+        if self.use_symbol_cache {
+            *self.visible_symbol_cache.borrow_mut() = Some(HashMap::new());
+        }
+        for item_id in item_ids {
+            if self.get_header(item_id).kind != ItemKind::Function {
+                continue;
+            }
+
+            let body = self.get_unresolved_body(item_id);
+            let new_body = self.resolve_idents_in_body(item_id, body, &mut diagnostics);
+            self.set_resolved_body(item_id, new_body);
+        }
+        *self.visible_symbol_cache.borrow_mut() = None;
+
+        self.check_unused_imports(&mut diagnostics);
+        if self.warn_empty {
+            for &id in self
+                .empty_functions
+                .iter()
+                .filter(|&&id| !self.is_skipped(id))
+            {
+                diagnostics.push(Diagnostic {
+                    message: format!("function `{}` is empty", self.full_path(id)),
+                    item: id,
+                    severity: Severity::Warning,
+                    span: self.get_header(id).span.clone(),
+                });
+            }
+        }
+
+        // Sorted so the output doesn't depend on the order we happened to visit things in.
+        diagnostics.sort_by(|a, b| (a.item, &a.message).cmp(&(b.item, &b.message)));
+        diagnostics.dedup();
+
+        self.diagnostics = diagnostics.clone();
+        diagnostics
+    }
+
+    // For when any problem at all, even a warning, should be treated as a failure.
+    pub fn resolve_idents_checked(&mut self) -> Result<(), Vec<Diagnostic>> {
+        let diagnostics = self.resolve_idents();
+        if diagnostics.is_empty() {
+            Ok(())
+        } else {
+            Err(diagnostics)
+        }
+    }
+
+    fn clear_import_bindings(&mut self) {
+        // Only declared items are left in the scopes.
+        for (id, scope) in self.scopes.iter_mut().enumerate() {
+            let headers = &self.headers;
+            scope.children.retain(|name, child_id| {
+                let child = &headers[child_id.0];
+                child.parent.0 == id && &child.name == name
+            });
+            scope.reexports.clear();
+            scope.glob_candidates.clear();
+        }
+
+        self.resolved_aliases.clear();
+    }
+
+    // Re-resolves a single item's imports and, if it's a function, its body, against the rest of
+    // the database as it currently stands. Nothing else is touched, so if the change affects what
+    // other items resolve to, it's up to the caller to re-resolve those too. The diagnostics are
+    // only returned, so `diagnostics` still gives the ones from the last `resolve_idents`.
+    pub fn resolve_item(&mut self, id: ItemId) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        self.resolve_imports(&[id], &mut diagnostics);
+
+        if self.get_header(id).kind == ItemKind::Function {
+            let body = self.get_unresolved_body(id);
+            let new_body = self.resolve_idents_in_body(id, body, &mut diagnostics);
+            self.set_resolved_body(id, new_body);
+        }
+
+        diagnostics
+    }
+
+    #[cfg(test)]
+    pub(crate) fn set_symbol_cache(&mut self, enabled: bool) {
+        self.use_symbol_cache = enabled;
+    }
+
+    pub fn set_strict_self(&mut self, strict: bool) {
+        self.strict_self = strict;
+    }
+
+    pub fn set_implicit_root_visibility(&mut self, implicit: bool) {
+        self.implicit_root_visibility = implicit;
+    }
+
+    pub fn set_warn_empty(&mut self, warn: bool) {
+        self.warn_empty = warn;
+    }
+
+    pub fn enable_feature(&mut self, name: &str) {
+        self.features.insert(name.to_owned());
+    }
+
+    // A guarded import whose feature is off is treated as if it weren't written.
+    fn import_enabled(&self, import: &UnresolvedImport) -> bool {
+        import
+            .feature
+            .as_ref()
+            .is_none_or(|(name, _)| self.features.contains(name))
+    }
+
+    // Turning tracing off throws away anything recorded so far.
+    pub fn set_trace(&mut self, trace: bool) {
+        let current = self.trace.get_mut();
+        if !trace {
+            *current = None;
+        } else if current.is_none() {
+            *current = Some(Vec::new());
+        }
+    }
+
+    // Everything recorded since tracing was turned on or the trace was last taken.
+    pub fn take_trace(&mut self) -> Vec<String> {
+        self.trace
+            .get_mut()
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    // The step is only built if tracing is on.
+    fn trace_step(&self, step: impl FnOnce() -> String) {
+        if let Some(trace) = &mut *self.trace.borrow_mut() {
+            trace.push(step());
+        }
+    }
+
+    // The diagnostics from the last call to `resolve_idents`.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    // Renders the diagnostic along with the line it points at, with the span underlined. The
+    // source must be the one the diagnostic's span came from. Nothing else about the database is
+    // needed, so this works for the diagnostics from a failed `resolve_str` too.
+    pub fn render_diagnostic(source: &str, diagnostic: &Diagnostic) -> String {
+        let span = &diagnostic.span;
+        let index = LineIndex::new(source);
+        let (line_num, column) = index.line_col(span.start);
+        let line_span = index.line_span(line_num);
+        let line_start = line_span.start;
+        let line = &source[line_span];
+
+        // A span running over multiple lines is only underlined up to the end of the first, and
+        // an empty one still gets a single caret.
+        let underline_end = span.end.clamp(span.start, line_start + line.len());
+        let underline_len = source[span.start..underline_end].chars().count().max(1);
+
+        let gutter = " ".repeat(line_num.to_string().len());
+        format!(
+            "{}: {}\n{}--> {}:{}\n{} |\n{} | {}\n{} | {}{}\n",
+            diagnostic.severity,
+            diagnostic.message,
+            gutter,
+            line_num,
+            column,
+            gutter,
+            line_num,
+            line,
+            gutter,
+            " ".repeat(column - 1),
+            "^".repeat(underline_len),
+        )
+    }
+
+    fn check_unused_imports(&self, diagnostics: &mut Vec<Diagnostic>) {
+        // Every ident in the program, along with the item it's looked up from.
+        let mut uses = Vec::new();
+        for header in self.live_headers() {
+            let imports = &self.get_scope(header.id).unresolved_imports;
+            uses.extend(
+                imports
+                    .iter()
+                    .filter(|i| self.import_enabled(i))
+                    .map(|i| (header.id, &i.path)),
+            );
+            uses.extend(
+                self.unresolved_aliases
+                    .get(&header.id)
+                    .map(|t| (header.id, t)),
+            );
+
+            for node in self.unresolved_bodies.get(&header.id).into_iter().flatten() {
+                match node {
+                    UnresolvedAST::Call { ident, args, chain } => {
+                        // Links are looked up in the previous call's item, so only their arguments
+                        // can use an import.
+                        uses.push((header.id, ident));
+                        uses.extend(args.iter().map(|a| (header.id, a)));
+                        for link in chain {
+                            uses.extend(link.args.iter().map(|a| (header.id, a)));
+                        }
+                    }
+                }
+            }
+        }
+
+        // An import is used if an ident that can see the importing scope starts with its name. Globs
+        // bring in too much to sensibly report, re-exports are for other modules, and one that
+        // failed to resolve has already been reported, so they're skipped.
+        for header in self.live_headers() {
+            for import in &self.get_scope(header.id).unresolved_imports {
+                let name = import.bound_name();
+                if import.glob
+                    || import.visibility == Visibility::Public
+                    || !self.import_enabled(import)
+                    || self
+                        .failed_imports
+                        .contains_key(&(header.id, name.to_owned()))
+                {
+                    continue;
+                }
+
+                let used = uses.iter().any(|&(user, ident)| {
+                    !std::ptr::eq(ident, &import.path)
+                        && ident.parts[0].0 == name
+                        && (user == header.id || self.outer_scopes(user).contains(&header.id))
+                });
+
+                if !used {
+                    diagnostics.push(Diagnostic {
+                        message: format!("unused import `{}`", import.path),
+                        item: header.id,
+                        severity: Severity::Warning,
+                        span: import.path.span.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    fn resolve_imports(&mut self, item_ids: &[ItemId], diagnostics: &mut Vec<Diagnostic>) {
+        // An import can depend on a name bound by another import, possibly in another scope, and
+        // possibly declared later. So rather than a single pass, we keep retrying the imports that
+        // failed until a whole round makes no progress. Anything left over is genuinely unresolvable.
+        // The imports are taken out of their scopes while we work, so they can be borrowed while
+        // the scopes are changed, and are put back at the end.
+        self.failed_imports
+            .retain(|(scope, _), _| !item_ids.contains(scope));
+        let imports: Vec<_> = item_ids
+            .iter()
+            .map(|&id| {
+                (
+                    id,
+                    std::mem::take(&mut self.scopes[id.0].unresolved_imports),
+                )
+            })
+            .collect();
+
+        // An alias's target is looked up just like an import, and imports can go through aliases,
+        // so they're resolved together. It's bound on the alias itself rather than in a scope.
+        let aliases: Vec<_> = item_ids
+            .iter()
+            .filter_map(|&id| {
+                let target = self.unresolved_aliases.remove(&id)?;
+                let import = UnresolvedImport {
+                    path: target,
+                    alias: Some(self.get_header(id).name.clone()),
+                    glob: false,
+                    except: Vec::new(),
+                    visibility: Visibility::Private,
+                    feature: None,
+                };
+                Some((id, import))
+            })
+            .collect();
+
+        let mut pending: Vec<_> = imports
+            .iter()
+            .flat_map(|(id, imports)| imports.iter().map(move |i| (*id, i)))
+            .filter(|(_, import)| self.import_enabled(import))
+            .chain(aliases.iter().map(|(id, import)| (*id, import)))
+            .collect();
+
+        // Resolved globs are re-applied every round, so they pick up names the source module
+        // gained from its own imports since.
+        let mut globs = Vec::new();
+
+        loop {
+            let mut progress = false;
+            let mut failed = Vec::new();
+            let mut failures = Vec::new();
+
+            for (item_id, import) in pending {
+                // A glob needs a module, anything else is bound in whichever namespaces it can be.
+                let namespace = if import.glob {
+                    Namespace::Type
+                } else {
+                    Namespace::Value
+                };
+                match self.resolve_single_ident_in(item_id, &import.path, namespace) {
+                    Ok(resolved_id) if import.glob => {
+                        progress = true;
+                        if self.get_header(resolved_id).kind == ItemKind::Module {
+                            globs.push((item_id, resolved_id, import));
+                        } else {
+                            diagnostics.push(Diagnostic {
+                                message: format!(
+                                    "cannot glob import from non-module `{}`",
+                                    import.path
+                                ),
+                                item: item_id,
+                                severity: Severity::Error,
+                                span: import.path.span.clone(),
+                            });
+                        }
+                    }
+                    Ok(resolved_id) if self.get_header(item_id).kind == ItemKind::Alias => {
+                        progress = true;
+                        self.resolved_aliases.insert(item_id, resolved_id);
+                    }
+                    Ok(resolved_id) => {
+                        progress = true;
+                        let name = import.bound_name().to_owned();
+
+                        // The path can name both a module and something else, in which case both
+                        // are brought in.
+                        let mut targets = vec![resolved_id];
+                        if let Ok(module_id) =
+                            self.resolve_single_ident_in(item_id, &import.path, Namespace::Type)
+                        {
+                            if module_id != resolved_id
+                                && self.get_header(module_id).kind == ItemKind::Module
+                            {
+                                targets.push(module_id);
+                            }
+                        }
+
+                        // Importing the scope itself, or something containing it, only adds a
+                        // roundabout way back to where we already are.
+                        if let Some(&target) = targets.iter().find(|&&t| self.is_within(item_id, t))
+                        {
+                            let relation = if target == item_id {
+                                "itself".to_owned()
+                            } else {
+                                format!("`{}`, which contains it", self.display_path(target))
+                            };
+                            diagnostics.push(Diagnostic {
+                                message: format!(
+                                    "import of `{}` in `{}` refers back to {}",
+                                    import.path,
+                                    self.display_path(item_id),
+                                    relation
+                                ),
+                                item: item_id,
+                                severity: Severity::Warning,
+                                span: import.path.span.clone(),
+                            });
+                        }
+
+                        for target in targets {
+                            let namespace = Namespace::of(self.get_header(target).kind);
+
+                            // Locals always shadow imports, whichever order they're written in,
+                            // so the import is just ignored. Unlike two locals with the same name,
+                            // that's unambiguous, so it's only a warning. Importing the local
+                            // itself is pointless, but harmless.
+                            let local = self.get_scope(item_id).children.get(&name, namespace);
+                            let is_local = |&l: &ItemId| {
+                                let header = self.get_header(l);
+                                header.parent == item_id && header.name == name && l != target
+                            };
+                            if let Some(local) = local.filter(is_local) {
+                                diagnostics.push(Diagnostic {
+                                    message: format!(
+                                        "import of `{}` is shadowed by local item `{}`",
+                                        import.path,
+                                        self.full_path(local)
+                                    ),
+                                    item: item_id,
+                                    severity: Severity::Warning,
+                                    span: import.path.span.clone(),
+                                });
+                                continue;
+                            }
+
+                            let headers = &self.headers;
+                            let scope = &mut self.scopes[item_id.0];
+                            if import.visibility == Visibility::Public {
+                                scope.reexports.insert(name.clone());
+                            }
+                            // An explicit import shadows anything a glob bound in the same
+                            // namespace before it got here.
+                            if let Some(candidates) = scope.glob_candidates.get_mut(&name) {
+                                candidates
+                                    .retain(|c| Namespace::of(headers[c.0].kind) != namespace);
+                                if candidates.is_empty() {
+                                    scope.glob_candidates.remove(&name);
+                                }
+                            }
+                            scope.add_child(name.clone(), target, namespace);
+
+                            // Top-level modules can be named from anywhere, so binding another
+                            // module under the same name quietly hides one in this scope.
+                            let root = self.crate_root(item_id);
+                            let top_level =
+                                self.get_scope(root).children.get(&name, Namespace::Type);
+                            if let Some(top_level) = top_level.filter(|&t| {
+                                namespace == Namespace::Type
+                                    && self.implicit_root_visibility
+                                    && item_id != root
+                                    && t != target
+                            }) {
+                                diagnostics.push(Diagnostic {
+                                    message: format!(
+                                        "import of `{}` shadows the top-level module `{}` in `{}`",
+                                        import.path,
+                                        self.full_path(top_level),
+                                        self.full_path(item_id)
+                                    ),
+                                    item: item_id,
+                                    severity: Severity::Warning,
+                                    span: import.path.span.clone(),
+                                });
+                            }
+                        }
+                    }
+                    Err(error) => {
+                        failed.push((item_id, import));
+                        failures.push(error.diagnostic);
+                    }
+                }
+            }
+
+            for (item_id, module_id, import) in &globs {
+                progress |= self.bind_glob_import(*item_id, *module_id, import);
+            }
+
+            pending = failed;
+            if !progress {
+                // Imports stuck waiting on each other get a single cycle diagnostic, instead of
+                // each reporting a missing symbol.
+                let cycles = self.find_import_cycles(&pending);
+                for cycle in &cycles {
+                    let participants: Vec<_> = cycle
+                        .iter()
+                        .map(|&i| format!("`{}` in {:?}", pending[i].1.path, pending[i].0))
+                        .collect();
+                    let is_alias =
+                        |&i: &usize| self.get_header(pending[i].0).kind == ItemKind::Alias;
+                    let what = if cycle.iter().all(is_alias) {
+                        "alias"
+                    } else {
+                        "import"
+                    };
+                    diagnostics.push(Diagnostic {
+                        message: format!("{} cycle: {}", what, participants.join(" -> ")),
+                        item: pending[cycle[0]].0,
+                        severity: Severity::Error,
+                        span: pending[cycle[0]].1.path.span.clone(),
+                    });
+                }
+
+                let failures = failures
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(i, _)| !cycles.iter().any(|c| c.contains(i)))
+                    .map(|(_, diag)| diag);
+                diagnostics.extend(failures);
+
+                // Globs don't bind a particular name, and an alias is still an item even when its
+                // target is missing.
+                for (item_id, import) in &pending {
+                    if !import.glob && self.get_header(*item_id).kind != ItemKind::Alias {
+                        self.failed_imports.insert(
+                            (*item_id, import.bound_name().to_owned()),
+                            import.path.to_string(),
+                        );
+                    }
+                }
+                break;
+            }
+        }
+
+        // The source module's own imports are bound by now, so anything it still lacks never
+        // existed. Leaving out a missing name does no harm, so it's only a warning.
+        for (item_id, module_id, import) in &globs {
+            for (name, span) in &import.except {
+                if !self.get_scope(*module_id).children.contains_key(name) {
+                    diagnostics.push(Diagnostic {
+                        message: format!(
+                            "`{}` is excluded from `{}.*`, but `{}` has no child `{}`",
+                            name,
+                            import.path,
+                            self.full_path(*module_id),
+                            name
+                        ),
+                        item: *item_id,
+                        severity: Severity::Warning,
+                        span: span.clone(),
+                    });
+                }
+            }
+        }
+
+        for (id, imports) in imports {
+            self.scopes[id.0].unresolved_imports = imports;
+        }
+        for (id, import) in aliases {
+            self.unresolved_aliases.insert(id, import.path);
+        }
+    }
+
+    fn find_import_cycles(&self, pending: &[(ItemId, &UnresolvedImport)]) -> Vec<Vec<usize>> {
+        // For each stuck import, find the other stuck imports that could bind a name it's waiting on.
+        // A glob could supply any name, so it's always a candidate. An import can never supply its
+        // own path though, so it isn't one of its own.
+        let deps: Vec<Vec<usize>> = pending
+            .iter()
+            .enumerate()
+            .map(|(i, (item_id, import))| {
+                let lookups = self.failed_lookups(*item_id, &import.path);
+                let candidates = pending.iter().enumerate().filter(|(j, (scope, other))| {
+                    *j != i
+                        && lookups.iter().any(|(s, name)| {
+                            s == scope && (other.glob || other.bound_name() == name)
+                        })
+                });
+                candidates.map(|(j, _)| j).collect()
+            })
+            .collect();
+
+        let mut cycles = Vec::new();
+        let mut done = vec![false; pending.len()];
+        let mut chain = Vec::new();
+        for start in 0..pending.len() {
+            find_cycles_from(start, &deps, &mut chain, &mut done, &mut cycles);
+        }
+
+        cycles
+    }
+
+    fn failed_lookups(&self, item_id: ItemId, path: &UnresolvedIdent) -> Vec<(ItemId, String)> {
+        // Rather than a diagnostic, we want every scope that was searched for the name that wasn't
+        // found. An alias that hasn't been resolved yet is waiting on its own target, so that's the
+        // name we're waiting on.
+        let (first, _) = &path.parts[0];
+        let Some(root) =
+            self.resolve_path_start(item_id, first, path_namespace(path, 0, Namespace::Value))
+        else {
+            if is_path_keyword(first) {
+                return Vec::new();
+            }
+
+            let scopes = self.searched_scopes(item_id);
+            return scopes.into_iter().map(|s| (s, first.clone())).collect();
+        };
+        if self.follow_alias(root).is_none() {
+            return vec![(root, self.name_of(root).to_owned())];
+        }
+
+        // Past the first part, the failure tells us where the descent stopped.
+        let Err(ResolveError {
+            matched,
+            last_good: Some(parent),
+            ..
+        }) = self.resolve_single_ident(item_id, path)
+        else {
+            return Vec::new();
+        };
+
+        let name = &path.parts[matched].0;
+        if self.get_header(parent).kind != ItemKind::Module {
+            return Vec::new();
+        }
+        match self.get_module_child(
+            parent,
+            name,
+            path_namespace(path, matched, Namespace::Value),
+        ) {
+            None => vec![(parent, name.clone())],
+            Some(child_id) if self.follow_alias(child_id).is_none() => {
+                vec![(child_id, self.name_of(child_id).to_owned())]
+            }
+            Some(_) => Vec::new(),
+        }
+    }
+
+    fn bind_glob_import(
+        &mut self,
+        item_id: ItemId,
+        module_id: ItemId,
+        import: &UnresolvedImport,
+    ) -> bool {
+        // Globs are the weakest binding: anything already in the scope, whether a local item or
+        // an explicit import, is kept. If two globs supply the same name, the first one is bound
+        // but both are remembered, so that using it can be reported as ambiguous.
+        // Only the children we'd be allowed to name directly are brought in, and a `pub` glob
+        // re-exports whatever it supplied. Anything in its `except` list is skipped.
+        let glob_children: Vec<_> = self
+            .get_scope(module_id)
+            .children
+            .iter()
+            .filter(|(name, _)| !import.except.iter().any(|(e, _)| e == *name))
+            .filter(|(name, &id)| self.is_accessible(item_id, module_id, name, id))
+            .map(|(name, &id)| (name.clone(), id, Namespace::of(self.get_header(id).kind)))
+            .collect();
+
+        // Each namespace is handled on its own, so a glob can supply a module and a function
+        // with the same name without them being ambiguous.
+        let mut bound_any = false;
+        let headers = &self.headers;
+        let scope = &mut self.scopes[item_id.0];
+        for (name, id, namespace) in glob_children {
+            let candidates = scope.glob_candidates.get(&name).into_iter().flatten();
+            let has_candidates = candidates
+                .map(|c| Namespace::of(headers[c.0].kind))
+                .any(|n| n == namespace);
+            if has_candidates {
+                let candidates = scope.glob_candidates.get_mut(&name).unwrap();
+                if !candidates.contains(&id) {
+                    candidates.push(id);
+                }
+            } else if scope.children.get(&name, namespace).is_none() {
+                scope
+                    .glob_candidates
+                    .entry(name.clone())
+                    .or_default()
+                    .push(id);
+                scope.add_child(name.clone(), id, namespace);
+                bound_any = true;
+            }
+
+            let supplied = scope.children.get(&name, namespace) == Some(id)
+                && scope
+                    .glob_candidates
+                    .get(&name)
+                    .is_some_and(|c| c.contains(&id));
+            if import.visibility == Visibility::Public && supplied {
+                bound_any |= scope.reexports.insert(name);
+            }
+        }
+
+        bound_any
+    }
+
+    fn resolve_idents_in_body(
+        &self,
+        current_func: ItemId,
+        body: &[UnresolvedAST],
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Vec<ResolvedAST> {
+        let mut new_body = Vec::new();
+
+        for node in body {
+            match node {
+                UnresolvedAST::Call { ident, args, chain } => {
+                    // Each argument is resolved separately, so each failure gets its own diagnostic.
+                    let callee = self.resolve_or_report(current_func, ident, diagnostics);
+                    let callee = self.check_callable(current_func, ident, callee, diagnostics);
+                    let args = args
+                        .iter()
+                        .map(|arg| self.resolve_or_report(current_func, arg, diagnostics))
+                        .collect();
+
+                    // Once a link fails, the rest of the chain has nothing to be looked up in, so
+                    // it's left unresolved without piling on more diagnostics.
+                    let mut previous = callee.clone();
+                    let mut resolved_chain = Vec::new();
+                    for UnresolvedChainLink { ident, args } in chain {
+                        let link = match previous {
+                            ResolvedIdent::Item(prev) => {
+                                match self.resolve_chain_link(current_func, prev, ident) {
+                                    Ok(id) => ResolvedIdent::Item(id),
+                                    Err(diagnostic) => {
+                                        diagnostics.push(diagnostic);
+                                        ResolvedIdent::Unresolved(ident.clone())
+                                    }
+                                }
+                            }
+                            ResolvedIdent::Unresolved(_) => {
+                                ResolvedIdent::Unresolved(ident.clone())
+                            }
+                        };
+                        let link = self.check_callable(current_func, ident, link, diagnostics);
+                        let args = args
+                            .iter()
+                            .map(|arg| self.resolve_or_report(current_func, arg, diagnostics))
+                            .collect();
+
+                        previous = link.clone();
+                        resolved_chain.push(ResolvedChainLink { ident: link, args });
+                    }
+
+                    new_body.push(ResolvedAST::Call {
+                        ident: callee,
+                        args,
+                        chain: resolved_chain,
+                    });
+                }
+            }
+        }
+
+        new_body
+    }
+
+    fn check_callable(
+        &self,
+        current_func: ItemId,
+        ident: &UnresolvedIdent,
+        callee: ResolvedIdent,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> ResolvedIdent {
+        // A call that doesn't resolve to something callable is treated as unresolved.
+        if let ResolvedIdent::Item(target) = callee {
+            let kind = self.get_header(target).kind;
+            if !matches!(kind, ItemKind::Function | ItemKind::Value) {
+                diagnostics.push(Diagnostic {
+                    message: format!("cannot call `{}`: it is a {}, not a function", ident, kind),
+                    item: current_func,
+                    severity: Severity::Error,
+                    span: ident.span.clone(),
+                });
+                return ResolvedIdent::Unresolved(ident.clone());
+            }
+        }
+
+        callee
+    }
+
+    fn resolve_chain_link(
+        &self,
+        current_func: ItemId,
+        previous: ItemId,
+        ident: &UnresolvedIdent,
+    ) -> Result<ItemId, Diagnostic> {
+        // A link is looked up among the items declared inside the one the previous call resolved
+        // to. Values have nothing inside them to look in.
+        let (name, span) = &ident.parts[0];
+        let previous_header = self.get_header(previous);
+        if !matches!(previous_header.kind, ItemKind::Function | ItemKind::Module) {
+            return Err(Diagnostic {
+                message: format!(
+                    "cannot call `{}` on the result of `{}`: it is a {}, not a module",
+                    name,
+                    self.full_path(previous),
+                    previous_header.kind
+                ),
+                item: current_func,
+                severity: Severity::Error,
+                span: span.clone(),
+            });
+        }
+
+        let scope = self.get_scope(previous);
+        let Some(child_id) = scope.children.get_preferring(name, Namespace::Value) else {
+            return Err(Diagnostic {
+                message: format!(
+                    "`{}` has no child `{}`{}",
+                    self.full_path(previous),
+                    name,
+                    suggestion(
+                        name,
+                        scope
+                            .children
+                            .map(Namespace::Value)
+                            .iter()
+                            .filter(|(n, &id)| self.is_accessible(current_func, previous, n, id))
+                            .map(|(n, _)| n.as_str())
+                            .collect()
+                    )
+                ),
+                item: current_func,
+                severity: Severity::Error,
+                span: span.clone(),
+            });
+        };
+
+        if !self.is_accessible(current_func, previous, name, child_id) {
+            return Err(Diagnostic {
+                message: format!(
+                    "`{}` is private to `{}`, in `{}`",
+                    name,
+                    self.full_path(previous),
+                    ident
+                ),
+                item: current_func,
+                severity: Severity::Error,
+                span: span.clone(),
+            });
+        }
+
+        self.follow_alias_in(current_func, ident, child_id, span)
+    }
+
+    fn resolve_or_report(
+        &self,
+        item_id: ItemId,
+        ident: &UnresolvedIdent,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> ResolvedIdent {
+        match self.resolve_single_ident(item_id, ident) {
+            Ok(id) => ResolvedIdent::Item(id),
+            Err(error) => {
+                diagnostics.push(error.diagnostic);
+                ResolvedIdent::Unresolved(ident.clone())
+            }
+        }
+    }
+
+    fn resolve_single_ident(
+        &self,
+        item_id: ItemId,
+        ident: &UnresolvedIdent,
+    ) -> Result<ItemId, ResolveError> {
+        self.resolve_single_ident_in(item_id, ident, Namespace::Value)
+    }
+
+    // Like `resolve_single_ident`, but with the namespace to look in first for the last part.
+    fn resolve_single_ident_in(
+        &self,
+        item_id: ItemId,
+        ident: &UnresolvedIdent,
+        last: Namespace,
+    ) -> Result<ItemId, ResolveError> {
+        self.trace_step(|| {
+            format!(
+                "resolving `{}` from `{}`",
+                ident,
+                self.display_path(item_id)
+            )
+        });
+
+        // The first part of the ident (e.g. "A2" in "A2.a_func") is where we start traversing *down*
+        // into the module tree.
+
+        // But first, we need to find out what item the first part refers to. To do that we need to
+        // traverse *up* the module tree, starting from the current item, looking for a matching ID.
+        // The current item here would be, for example, a function that we're resolving the body for.
+        // The "crate", "super" and "self" path segments short-circuit that search, but only make sense
+        // at the start of a path.
+        if let Some((keyword, span)) = ident.parts[1..].iter().find(|(p, _)| is_path_keyword(p)) {
+            return Err(ResolveError::new(
+                0,
+                None,
+                Diagnostic {
+                    message: format!(
+                        "`{}` is only valid at the start of a path, in `{}`",
+                        keyword, ident
+                    ),
+                    item: item_id,
+                    severity: Severity::Error,
+                    span: span.clone(),
+                },
+            ));
+        }
+
+        let (first, first_span) = &ident.parts[0];
+
+        // In strict mode an item refers to itself with a bare `self`. Followed by more parts, it's
+        // still the enclosing module, as items other than modules have no children to name.
+        if self.strict_self && ident.parts.len() == 1 && first == "self" {
+            return Ok(item_id);
+        }
+
+        let first_namespace = path_namespace(ident, 0, last);
+        if let Some(candidates) = self.glob_ambiguity(item_id, first, first_namespace) {
+            let mut paths: Vec<_> = candidates
+                .iter()
+                .map(|&c| format!("`{}`", self.full_path(c)))
+                .collect();
+            paths.sort();
+            return Err(ResolveError::new(
+                0,
+                None,
+                Diagnostic {
+                    message: format!(
+                        "`{}` is ambiguous, in `{}`: it could be any of {}",
+                        first,
+                        ident,
+                        paths.join(", ")
+                    ),
+                    item: item_id,
+                    severity: Severity::Error,
+                    span: first_span.clone(),
+                },
+            ));
+        }
+
+        let Some(root) = self.resolve_path_start(item_id, first, first_namespace) else {
+            // The only keyword that can fail is `super`, when we're already at the root.
+            let failed_import = std::iter::once(item_id)
+                .chain(self.outer_scopes(item_id))
+                .find_map(|scope| self.failed_imports.get(&(scope, first.clone())));
+            let message = if first == "super" {
+                format!("`super` in `{}` has no parent module to refer to", ident)
+            } else if let Some(path) = failed_import {
+                format!(
+                    "`{}` comes from the import `{}`, which failed to resolve",
+                    first, path
+                )
+            } else if self
+                .get_scope(self.crate_root(item_id))
+                .children
+                .contains_key(first.as_str())
+            {
+                format!(
+                    "symbol `{}` not found; top-level items need to be imported, or named with `crate.{}`",
+                    first, first
+                )
+            } else {
+                format!(
+                    "symbol `{}` not found{}",
+                    first,
+                    suggestion(first, self.visible_names(item_id, first_namespace))
+                )
+            };
+            return Err(ResolveError::new(
+                0,
+                None,
+                Diagnostic {
+                    message,
+                    item: item_id,
+                    severity: Severity::Error,
+                    span: first_span.clone(),
+                },
+            ));
+        };
+
+        // Disabling the shortcut isn't enough on its own, as an item can usually also be found
+        // among its parent's children.
+        if self.strict_self && root == item_id && !is_path_keyword(first) {
+            return Err(ResolveError::new(
+                0,
+                None,
+                Diagnostic {
+                    message: format!(
+                        "`{}` refers to the current item by name, use `self` instead",
+                        first
+                    ),
+                    item: item_id,
+                    severity: Severity::Error,
+                    span: first_span.clone(),
+                },
+            ));
+        }
+
+        // Now that we know what the root is, we can start traversing down the tree into its children.
+        // Aliases are followed as we go, so the result is never an alias itself.
+        let mut current_item = self
+            .follow_alias_in(item_id, ident, root, first_span)
+            .map_err(|d| ResolveError::new(0, None, d))?;
+        for (matched, (sub_ident, sub_span)) in ident.parts.iter().enumerate().skip(1) {
+            let current_header = self.get_header(current_item);
+            if current_header.kind != ItemKind::Module {
+                return Err(ResolveError::new(
+                    matched,
+                    Some(current_item),
+                    Diagnostic {
+                        message: format!(
+                            "cannot resolve `{}` in `{}`: `{}` is a {}, not a module",
+                            sub_ident, ident, current_header.name, current_header.kind
+                        ),
+                        item: item_id,
+                        severity: Severity::Error,
+                        span: sub_span.clone(),
+                    },
+                ));
+            }
+
+            let namespace = path_namespace(ident, matched, last);
+            let Some(child_id) = self.get_module_child(current_item, sub_ident, namespace) else {
+                // Going through `crate` skips the usual search, so if it fails it's always because
+                // the top-level item doesn't exist.
+                let what = if current_item == self.crate_root(item_id) {
+                    format!("there is no top-level item `{}`, in `{}`", sub_ident, ident)
+                } else {
+                    format!(
+                        "resolved `{}` but `{}` has no child `{}`",
+                        ident.prefix(matched),
+                        ident.parts[matched - 1].0,
+                        sub_ident,
+                    )
+                };
+                return Err(ResolveError::new(
+                    matched,
+                    Some(current_item),
+                    Diagnostic {
+                        message: format!(
+                            "{}{}",
+                            what,
+                            suggestion(
+                                sub_ident,
+                                self.get_scope(current_item)
+                                    .children
+                                    .map(namespace)
+                                    .iter()
+                                    .filter(|(name, &id)| {
+                                        self.is_accessible(item_id, current_item, name, id)
+                                    })
+                                    .map(|(name, _)| name.as_str())
+                                    .collect()
+                            )
+                        ),
+                        item: item_id,
+                        severity: Severity::Error,
+                        span: sub_span.clone(),
+                    },
+                ));
+            };
+
+            if !self.is_accessible(item_id, current_item, sub_ident, child_id) {
+                return Err(ResolveError::new(
+                    matched,
+                    Some(current_item),
+                    Diagnostic {
+                        message: format!(
+                            "`{}` is private to `{}`, in `{}`",
+                            sub_ident,
+                            self.full_path(current_item),
+                            ident
+                        ),
+                        item: item_id,
+                        severity: Severity::Error,
+                        span: sub_span.clone(),
+                    },
+                ));
+            }
+
+            current_item = self
+                .follow_alias_in(item_id, ident, child_id, sub_span)
+                .map_err(|d| ResolveError::new(matched, Some(current_item), d))?;
+        }
+
+        // Once we've got through the sub-idents, we're done.
+        Ok(current_item)
+    }
+
+    fn visible_names(&self, item_id: ItemId, namespace: Namespace) -> Vec<&str> {
+        // Everything `find_visible_symbol` would look through, but only in the namespace we're
+        // after, as suggesting a module for a call doesn't help.
+        let header = self.get_header(item_id);
+        let mut names = Vec::new();
+        if Namespace::of(header.kind) == namespace {
+            names.push(header.name.as_str());
+        }
+        for scope_id in self.searched_scopes(item_id) {
+            let children = self.get_scope(scope_id).children.map(namespace);
+            names.extend(children.keys().map(|n| n.as_str()));
+        }
+        if let Some(parent) = self
+            .sibling_scope(item_id)
+            .filter(|_| namespace == Namespace::Type)
+        {
+            let siblings = self.get_scope(parent).children.types.iter();
+            names.extend(
+                siblings
+                    .filter(|(_, s)| self.get_header(**s).parent == parent)
+                    .map(|(n, _)| n.as_str()),
+            );
+        }
+
+        names
+    }
+
+    fn follow_alias(&self, id: ItemId) -> Option<ItemId> {
+        // Anything that isn't an alias is its own target. An alias only has one once its target
+        // has been resolved, which also followed any aliases in the way.
+        if self.get_header(id).kind != ItemKind::Alias {
+            return Some(id);
+        }
+
+        self.resolved_aliases.get(&id).copied()
+    }
+
+    fn follow_alias_in(
+        &self,
+        item_id: ItemId,
+        ident: &UnresolvedIdent,
+        id: ItemId,
+        span: &Range<usize>,
+    ) -> Result<ItemId, Diagnostic> {
+        self.follow_alias(id).ok_or_else(|| Diagnostic {
+            message: format!(
+                "alias `{}` could not be resolved, in `{}`",
+                self.full_path(id),
+                ident
+            ),
+            item: item_id,
+            severity: Severity::Error,
+            span: span.clone(),
+        })
+    }
+
+    pub fn lookup_path(&self, parts: &[&str]) -> Option<ItemId> {
+        // Unlike resolving an ident, there's no current item to search upward from, so we always
+        // start from the root and only walk down.
+        let namespace = |i: usize| {
+            if i + 1 < parts.len() {
+                Namespace::Type
+            } else {
+                Namespace::Value
+            }
+        };
+        parts
+            .iter()
+            .enumerate()
+            .try_fold(self.root, |current, (i, part)| {
+                self.get_module_child(current, part, namespace(i))
+            })
+    }
+
+    pub fn items_of_kind(&self, kind: ItemKind) -> impl Iterator<Item = ItemId> + '_ {
+        // The root is only there to hold the top-level items, so it isn't included.
+        self.live_headers()
+            .filter(move |h| h.id != self.root && h.kind == kind)
+            .map(|h| h.id)
+    }
+
+    pub fn children_of(&self, id: ItemId) -> impl Iterator<Item = (&str, ItemId)> {
+        let mut children: Vec<_> = self
+            .get_scope(id)
+            .children
+            .iter()
+            .map(|(name, &child_id)| (name.as_str(), child_id))
+            .collect();
+        children.sort_unstable();
+
+        children.into_iter()
+    }
+
+    fn get_module_child(
+        &self,
+        item_id: ItemId,
+        name: &str,
+        namespace: Namespace,
+    ) -> Option<ItemId> {
+        if self.get_header(item_id).kind != ItemKind::Module {
+            return None;
+        }
+
+        self.get_scope(item_id)
+            .children
+            .get_preferring(name, namespace)
+    }
+
+    fn is_accessible(&self, from: ItemId, module_id: ItemId, name: &str, child_id: ItemId) -> bool {
+        // Anything inside a module can see all of its children. From outside, only public items
+        // declared in the module are visible, along with names it re-exported with `pub using`.
+        if self.is_within(from, module_id) {
+            return true;
+        }
+
+        let child = self.get_header(child_id);
+        if child.parent == module_id && child.name == name {
+            child.visibility == Visibility::Public
+        } else {
+            self.get_scope(module_id).reexports.contains(name)
+        }
+    }
+
+    fn is_within(&self, item_id: ItemId, ancestor: ItemId) -> bool {
+        let mut current = item_id;
+        loop {
+            if current == ancestor {
+                return true;
+            }
+            if current == self.root {
+                return false;
+            }
+            current = self.get_header(current).parent;
+        }
+    }
+
+    fn resolve_path_start(
+        &self,
+        item_id: ItemId,
+        first: &str,
+        namespace: Namespace,
+    ) -> Option<ItemId> {
+        // `self` is the module we're in: a module's own, or for a function (however deeply nested)
+        // the module it's declared in, so `self.sibling` finds the function's siblings.
+        match first {
+            "crate" => Some(self.crate_root(item_id)),
+            "self" => Some(self.enclosing_module(item_id)),
+            "super" => {
+                let module = self.enclosing_module(item_id);
+                (module != self.crate_root(item_id)).then(|| self.get_header(module).parent)
+            }
+            name => self.get_visible_symbol(item_id, name, namespace),
+        }
+    }
+
+    fn enclosing_module(&self, item_id: ItemId) -> ItemId {
+        // A module is its own enclosing module, anything else belongs to its parent's.
+        let mut current = item_id;
+        while self.get_header(current).kind != ItemKind::Module {
+            current = self.get_header(current).parent;
+        }
+
+        current
+    }
+
+    fn outer_scopes(&self, item_id: ItemId) -> Vec<ItemId> {
+        let mut scopes = Vec::new();
+        let mut current = item_id;
+        while self.get_header(current).kind != ItemKind::Module {
+            current = self.get_header(current).parent;
+            scopes.push(current);
+        }
+
+        scopes
+    }
+
+    fn get_visible_symbol(
+        &self,
+        item_id: ItemId,
+        name: &str,
+        namespace: Namespace,
+    ) -> Option<ItemId> {
+        let key = (item_id, name.to_owned(), namespace);
+        if let Some(cache) = &*self.visible_symbol_cache.borrow() {
+            if let Some(&symbol) = cache.get(&key) {
+                self.trace_step(|| {
+                    format!("cached: `{}` from `{}`", name, self.display_path(item_id))
+                });
+                return symbol;
+            }
+        }
+
+        let symbol = self
+            .find_visible_symbol(item_id, name, namespace)
+            .or_else(|| self.find_visible_symbol(item_id, name, namespace.other()));
+
+        if let Some(cache) = &mut *self.visible_symbol_cache.borrow_mut() {
+            cache.insert(key, symbol);
+        }
+
+        symbol
+    }
+
+    fn find_visible_symbol(
+        &self,
+        item_id: ItemId,
+        name: &str,
+        namespace: Namespace,
+    ) -> Option<ItemId> {
+        // First, we check ourselves. It's valid for an item to refer to itself, so that should
+        // come first, unless we're being strict about it.
+        let own_header = self.get_header(item_id);
+        let is_self = name == own_header.name
+            && Namespace::of(own_header.kind) == namespace
+            && !self.strict_self;
+        self.trace_step(|| {
+            format!(
+                "checked-self: `{}` {} `{}`",
+                self.display_path(item_id),
+                if is_self { "is" } else { "isn't" },
+                name
+            )
+        });
+        if is_self {
+            return Some(item_id);
+        }
+
+        let found = self.find_symbol_scope(item_id, name, namespace);
+        self.trace_scopes(item_id, name, namespace, found);
+        self.get_scope(found?).children.get(name, namespace)
+    }
+
+    fn trace_scopes(
+        &self,
+        item_id: ItemId,
+        name: &str,
+        namespace: Namespace,
+        found: Option<ItemId>,
+    ) {
+        if self.trace.borrow().is_none() {
+            return;
+        }
+
+        // The scopes `find_symbol_scope` went through, up to the one it stopped at. Siblings are
+        // checked just before the root.
+        let mut scopes = self.searched_scopes(item_id);
+        let sibling_scope = self
+            .sibling_scope(item_id)
+            .filter(|_| namespace == Namespace::Type);
+        if let Some(parent) = sibling_scope {
+            let before_root = scopes.len() - usize::from(self.implicit_root_visibility);
+            scopes.insert(before_root, parent);
+        }
+
+        for scope_id in scopes {
+            let step = if scope_id == item_id {
+                "checked-children"
+            } else if scope_id == self.crate_root(item_id) {
+                "checked-root"
+            } else if Some(scope_id) == sibling_scope {
+                "checked-siblings"
+            } else {
+                "checked-parent"
+            };
+            let result = if found == Some(scope_id) {
+                "found"
+            } else {
+                "no"
+            };
+            let what = match namespace {
+                Namespace::Type => "module",
+                Namespace::Value => "item",
+            };
+            self.trace_step(|| {
+                format!(
+                    "{}: {} {} `{}` in `{}`",
+                    step,
+                    result,
+                    what,
+                    name,
+                    self.display_path(scope_id)
+                )
+            });
+            if found == Some(scope_id) {
+                break;
+            }
+        }
+    }
+
+    fn glob_ambiguity(
+        &self,
+        item_id: ItemId,
+        name: &str,
+        namespace: Namespace,
+    ) -> Option<Vec<ItemId>> {
+        if is_path_keyword(name) {
+            return None;
+        }
+
+        // The candidates that matter are the ones in whichever namespace the name is found in.
+        let own_header = self.get_header(item_id);
+        for namespace in [namespace, namespace.other()] {
+            if name == own_header.name
+                && Namespace::of(own_header.kind) == namespace
+                && !self.strict_self
+            {
+                return None;
+            }
+
+            let Some(scope_id) = self.find_symbol_scope(item_id, name, namespace) else {
+                continue;
+            };
+            let candidates: Vec<_> = self
+                .get_scope(scope_id)
+                .glob_candidates
+                .get(name)?
+                .iter()
+                .copied()
+                .filter(|&c| Namespace::of(self.get_header(c).kind) == namespace)
+                .collect();
+            return (candidates.len() > 1).then_some(candidates);
+        }
+
+        None
+    }
+
+    fn find_symbol_scope(
+        &self,
+        item_id: ItemId,
+        name: &str,
+        namespace: Namespace,
+    ) -> Option<ItemId> {
+        // Now we check our children.
+        let own_scope = self.get_scope(item_id);
+        if own_scope.children.get(name, namespace).is_some() {
+            return Some(item_id);
+        }
+
+        // If we are not a module, we then check our enclosing scopes' children, working outwards
+        // through any enclosing functions until we get to a module.
+        // The reason we don't traverse up if we're a module, or traverse upward
+        // past our enclosing module is so that we only see symbols imported into
+        // *our* module.
+        for scope_id in self.outer_scopes(item_id) {
+            if self
+                .get_scope(scope_id)
+                .children
+                .get(name, namespace)
+                .is_some()
+            {
+                return Some(scope_id);
+            }
+        }
+
+        // A module can also see its sibling modules by name, so anything inside it can too. Only
+        // the modules declared in the parent count, not its other items or anything it imported,
+        // so beyond its neighbours a module still only sees what was imported into it. That
+        // includes private siblings, which it could already reach as it's inside their parent.
+        if namespace == Namespace::Type {
+            if let Some(parent) = self.sibling_scope(item_id) {
+                let sibling = self.get_scope(parent).children.get(name, namespace);
+                if sibling.is_some_and(|s| self.get_header(s).parent == parent) {
+                    return Some(parent);
+                }
+            }
+        }
+
+        // If we still haven't found a symbol, we check the root, unless that's been turned off.
+        // In the example file, the roots would be A1 and B1.
+        if !self.implicit_root_visibility {
+            return None;
+        }
+        let root = self.crate_root(item_id);
+        self.get_scope(root)
+            .children
+            .get(name, namespace)
+            .is_some()
+            .then_some(root)
+    }
+
+    // The parent of our enclosing module, whose modules are our module's siblings. Top-level
+    // modules are left to `implicit_root_visibility`.
+    fn sibling_scope(&self, item_id: ItemId) -> Option<ItemId> {
+        let module = self.enclosing_module(item_id);
+        let parent = self.get_header(module).parent;
+        (parent != self.crate_root(item_id)).then_some(parent)
+    }
+
+    fn searched_scopes(&self, item_id: ItemId) -> Vec<ItemId> {
+        // The scopes `find_symbol_scope` looks through, in order.
+        let mut scopes = vec![item_id];
+        scopes.extend(self.outer_scopes(item_id));
+        if self.implicit_root_visibility {
+            scopes.push(self.crate_root(item_id));
+        }
+
+        scopes
+    }
+
+    pub fn view(&self) -> ResolvedView<'_> {
+        ResolvedView { database: self }
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn snapshot(&self) -> DatabaseSnapshot<'_> {
+        DatabaseSnapshot {
+            headers: self.headers.iter().filter(|h| !h.removed).collect(),
+            resolved_bodies: &self.resolved_bodies,
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        // Nothing in the snapshot can fail to serialize.
+        serde_json::to_string(&self.snapshot()).unwrap()
+    }
+
+    // The diagnostics from the last resolution, for tools rather than people. `render_diagnostic`
+    // is the readable version.
+    #[cfg(feature = "serde")]
+    pub fn diagnostics_json(&self, source: &str) -> String {
+        let index = LineIndex::new(source);
+        let position = |offset: usize| {
+            let (line, column) = index.line_col(offset);
+            JsonPosition {
+                offset,
+                line,
+                column,
+            }
+        };
+
+        let diagnostics: Vec<_> = self
+            .diagnostics
+            .iter()
+            .map(|d| JsonDiagnostic {
+                message: &d.message,
+                severity: d.severity.to_string(),
+                item: self.display_path(d.item),
+                start: position(d.span.start),
+                end: position(d.span.end),
+            })
+            .collect();
+
+        // Nothing here can fail to serialize.
+        serde_json::to_string(&diagnostics).unwrap()
+    }
+
+    pub fn references_to(&self, target: ItemId) -> Vec<ItemId> {
+        self.resolved_bodies
+            .iter()
+            .filter(|(_, body)| {
+                body.iter().any(|node| match node {
+                    ResolvedAST::Call { ident, chain, .. } => std::iter::once(ident)
+                        .chain(chain.iter().map(|link| &link.ident))
+                        .any(|ident| matches!(ident, ResolvedIdent::Item(id) if *id == target)),
+                })
+            })
+            .map(|(&caller, _)| caller)
+            .collect()
+    }
+
+    // Every resolved call in the function's body, in order. Each link in a chained call counts as
+    // a call of its own.
+    fn calls_in(&self, caller: ItemId) -> impl Iterator<Item = ItemId> + '_ {
+        let body = self.resolved_bodies.get(&caller).into_iter().flatten();
+        body.flat_map(|node| {
+            let ResolvedAST::Call { ident, chain, .. } = node;
+            std::iter::once(ident).chain(chain.iter().map(|link| &link.ident))
+        })
+        .filter_map(|ident| match *ident {
+            ResolvedIdent::Item(target) => Some(target),
+            ResolvedIdent::Unresolved(_) => None,
+        })
+    }
+
+    pub fn walk_resolved(&self, visitor: &mut impl ResolvedVisitor) {
+        for &caller in self.resolved_bodies.keys() {
+            for target in self.calls_in(caller) {
+                visitor.visit_call(caller, target);
+            }
+        }
+    }
+
+    // Functions that call themselves by name. Recursion through other functions isn't included.
+    pub fn direct_recursion(&self) -> Vec<ItemId> {
+        self.resolved_bodies
+            .keys()
+            .copied()
+            .filter(|&id| self.calls_in(id).any(|target| target == id))
+            .collect()
+    }
+
+    // Groups of functions that call each other in a loop, along with any function calling itself.
+    // Each group's members are sorted by id.
+    pub fn call_cycles(&self) -> Vec<Vec<ItemId>> {
+        let functions: Vec<_> = self.resolved_bodies.keys().copied().collect();
+        let node_of: HashMap<_, _> = functions.iter().enumerate().map(|(i, &f)| (f, i)).collect();
+        let deps: Vec<Vec<usize>> = functions
+            .iter()
+            .map(|&f| {
+                let targets = self.calls_in(f).filter_map(|t| node_of.get(&t).copied());
+                targets.collect()
+            })
+            .collect();
+
+        let mut state = SccState::new(functions.len());
+        for node in 0..functions.len() {
+            if state.index[node].is_none() {
+                strong_connect(node, &deps, &mut state);
+            }
+        }
+
+        let mut cycles: Vec<Vec<ItemId>> = state
+            .components
+            .into_iter()
+            .filter(|c| c.len() > 1 || deps[c[0]].contains(&c[0]))
+            .map(|c| c.into_iter().map(|n| functions[n]).collect())
+            .collect();
+        for cycle in &mut cycles {
+            cycle.sort_unstable();
+        }
+        cycles.sort_unstable();
+        cycles
+    }
+
+    // Every function the entry point calls, directly or not, including the entry point itself.
+    // Empty if there's no entry point.
+    pub fn reachable_from_entry(&self) -> HashSet<ItemId> {
+        let mut reachable = HashSet::new();
+        let mut stack: Vec<_> = self.entry().into_iter().collect();
+        while let Some(id) = stack.pop() {
+            if !reachable.insert(id) {
+                continue;
+            }
+
+            stack.extend(self.calls_in(id));
+        }
+
+        reachable
+    }
+
+    // Items that nothing refers to, after resolution. Top-level items are treated as entry points,
+    // so they're never orphans, and neither is anything containing an item that's used.
+    pub fn orphan_items(&self) -> Vec<ItemId> {
+        let mut used = HashSet::new();
+
+        // An item calling itself doesn't make it any less dead.
+        for (&caller, body) in &self.resolved_bodies {
+            for node in body {
+                match node {
+                    ResolvedAST::Call { ident, args, chain } => {
+                        let links = chain
+                            .iter()
+                            .flat_map(|l| std::iter::once(&l.ident).chain(&l.args));
+                        for ident in std::iter::once(ident).chain(args).chain(links) {
+                            if let ResolvedIdent::Item(id) = *ident {
+                                if id != caller {
+                                    used.insert(id);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for header in self.live_headers() {
+            for import in &self.get_scope(header.id).unresolved_imports {
+                if !self.import_enabled(import) {
+                    continue;
+                }
+                if let Ok(id) = self.resolve_single_ident(header.id, &import.path) {
+                    used.insert(id);
+                }
+            }
+        }
+        used.extend(self.resolved_aliases.values());
+
+        let mut used_or_containing = HashSet::new();
+        for id in used {
+            let mut current = id;
+            while current != self.root && used_or_containing.insert(current) {
+                current = self.get_header(current).parent;
+            }
+        }
+
+        self.live_headers()
+            .filter(|h| h.id != self.root && h.parent != self.root)
+            .filter(|h| !used_or_containing.contains(&h.id))
+            .map(|h| h.id)
+            .collect()
+    }
+
+    pub fn to_dot(&self) -> String {
+        use std::fmt::Write;
+
+        let mut out = String::from("digraph {\n");
+
+        for header in self.live_headers().skip(1) {
+            writeln!(
+                out,
+                "    {} [label=\"{} ({:?})\"];",
+                header.id.0, header.name, header.kind
+            )
+            .unwrap();
+        }
+
+        for header in self.live_headers().skip(1) {
+            if header.parent != self.root {
+                writeln!(out, "    {} -> {};", header.parent.0, header.id.0).unwrap();
+            }
+        }
+
+        for (caller, body) in &self.resolved_bodies {
+            for node in body {
+                let ResolvedAST::Call { ident, chain, .. } = node;
+                for ident in std::iter::once(ident).chain(chain.iter().map(|link| &link.ident)) {
+                    if let ResolvedIdent::Item(target) = ident {
+                        writeln!(out, "    {} -> {} [style=dashed];", caller.0, target.0).unwrap();
+                    }
+                }
+            }
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    pub fn print_headers(&self) {
+        eprintln!(" == Headers ==");
+        eprintln!("{:#?}\n\n", self.headers);
+    }
+
+    pub fn print_unresolved_ast(&self) {
+        eprintln!(" == Unresolved ASTs ==");
+        eprintln!("{:#?}\n\n", self.unresolved_bodies);
+    }
+
+    pub fn print_resolved_ast(&self) {
+        eprintln!(" == Resolved ASTs ==");
         eprintln!("{:#?}", self.resolved_bodies);
     }
+
+    pub fn print_resolved_ast_pretty(&self) {
+        eprintln!(" == Resolved ASTs ==");
+        eprint!("{}", self.resolved_ast_pretty());
+    }
+
+    pub fn print_tree(&self, order: ChildOrder) {
+        eprintln!(" == Item Tree ==");
+        eprint!("{}", self.tree(order));
+    }
+
+    pub fn tree(&self, order: ChildOrder) -> String {
+        let mut out = String::new();
+        self.write_tree(&mut out, self.root, 0, order);
+        out
+    }
+
+    fn write_tree(&self, out: &mut String, id: ItemId, depth: usize, order: ChildOrder) {
+        use std::fmt::Write;
+
+        // Only declared items are listed, not anything imported.
+        let mut children = self.get_scope(id).declaration_order.clone();
+        if order == ChildOrder::Alphabetical {
+            children.sort_by(|&a, &b| self.name_of(a).cmp(self.name_of(b)));
+        }
+
+        for child in children {
+            let header = self.get_header(child);
+            writeln!(
+                out,
+                "{}{} [{:?}]",
+                "    ".repeat(depth),
+                header.name,
+                header.kind
+            )
+            .unwrap();
+            self.write_tree(out, child, depth + 1, order);
+        }
+    }
+
+    pub fn print_symbol_table(&self) {
+        eprintln!(" == Symbol Table ==");
+        eprint!("{}", self.symbol_table());
+    }
+
+    pub fn symbol_table(&self) -> String {
+        use std::fmt::Write;
+
+        let mut out = String::new();
+        for header in self.live_headers() {
+            writeln!(
+                out,
+                "{:?}: {} [{:?}]",
+                header.id,
+                self.display_path(header.id),
+                header.kind
+            )
+            .unwrap();
+        }
+
+        // Imports are looked up again rather than remembered, as resolution has finished and the
+        // scopes won't change.
+        for header in self.live_headers() {
+            let imports = &self.get_scope(header.id).unresolved_imports;
+            if imports.is_empty() {
+                continue;
+            }
+
+            writeln!(out, "Imports in {}:", self.display_path(header.id)).unwrap();
+            for import in imports {
+                let name = if import.glob && !import.except.is_empty() {
+                    let except: Vec<_> = import.except.iter().map(|(e, _)| e.as_str()).collect();
+                    format!("{}.* except {}", import.path, except.join(", "))
+                } else if import.glob {
+                    format!("{}.*", import.path)
+                } else {
+                    import.bound_name().to_owned()
+                };
+                let target = match (&import.feature, self.import_enabled(import)) {
+                    (Some((feature, _)), false) => format!("<disabled: needs `{}`>", feature),
+                    _ => match self.resolve_single_ident(header.id, &import.path) {
+                        Ok(id) => self.display_path(id),
+                        Err(_) => format!("<unresolved: {}>", import.path),
+                    },
+                };
+                writeln!(out, "    {} -> {}", name, target).unwrap();
+            }
+        }
+
+        out
+    }
+
+    pub fn print_statistics(&self) {
+        eprintln!(" == Statistics ==");
+        eprint!("{}", self.statistics());
+    }
+
+    // Counted from scratch each time, so it stays right after items are added or removed.
+    pub fn statistics(&self) -> String {
+        use std::fmt::Write;
+
+        // The root isn't a real module, so it isn't counted, but its imports are.
+        let count_kind = |kind| {
+            self.live_headers()
+                .filter(|h| h.kind == kind && h.id != self.root)
+                .count()
+        };
+        let imports: usize = self
+            .live_headers()
+            .map(|h| self.get_scope(h.id).unresolved_imports.len())
+            .sum();
+
+        let mut resolved_calls = 0;
+        let mut unresolved = 0;
+        let mut count = |ident: &ResolvedIdent, is_call: bool| match ident {
+            ResolvedIdent::Item(_) if is_call => resolved_calls += 1,
+            ResolvedIdent::Item(_) => {}
+            ResolvedIdent::Unresolved(_) => unresolved += 1,
+        };
+        for node in self.resolved_bodies.values().flatten() {
+            match node {
+                ResolvedAST::Call { ident, args, chain } => {
+                    count(ident, true);
+                    args.iter().for_each(|arg| count(arg, false));
+                    for link in chain {
+                        count(&link.ident, true);
+                        link.args.iter().for_each(|arg| count(arg, false));
+                    }
+                }
+            }
+        }
+
+        let mut out = String::new();
+        writeln!(out, "modules: {}", count_kind(ItemKind::Module)).unwrap();
+        writeln!(out, "functions: {}", count_kind(ItemKind::Function)).unwrap();
+        writeln!(out, "imports: {}", imports).unwrap();
+        writeln!(out, "resolved calls: {}", resolved_calls).unwrap();
+        writeln!(out, "unresolved references: {}", unresolved).unwrap();
+        out
+    }
+
+    pub fn resolved_ast_pretty(&self) -> String {
+        use std::fmt::Write;
+
+        let mut out = String::new();
+        for (&func_id, body) in &self.resolved_bodies {
+            let func_path = self.full_path(func_id);
+            writeln!(out, "{}", func_path).unwrap();
+
+            for node in body {
+                match node {
+                    ResolvedAST::Call { ident, args, chain } => {
+                        write!(out, "    {}", func_path).unwrap();
+                        let links = chain.iter().map(|link| (&link.ident, &link.args));
+                        for (ident, args) in std::iter::once((ident, args)).chain(links) {
+                            write!(out, " -> {}", self.pretty_ident(ident)).unwrap();
+                            if !args.is_empty() {
+                                let args: Vec<_> =
+                                    args.iter().map(|a| self.pretty_ident(a)).collect();
+                                write!(out, "({})", args.join(", ")).unwrap();
+                            }
+                        }
+                        out.push('\n');
+                    }
+                }
+            }
+        }
+
+        out
+    }
+
+    fn pretty_ident(&self, ident: &ResolvedIdent) -> String {
+        match ident {
+            ResolvedIdent::Item(id) => self.full_path(*id),
+            ResolvedIdent::Unresolved(ident) => format!("<unresolved: {}>", ident),
+        }
+    }
+}
+
+fn unresolve_removed(
+    headers: &[ItemHeader],
+    ident: &mut ResolvedIdent,
+    original: &UnresolvedIdent,
+) {
+    if matches!(ident, ResolvedIdent::Item(id) if headers[id.0].removed) {
+        *ident = ResolvedIdent::Unresolved(original.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+
+    fn parse(source: &str) -> Database {
+        let mut database = Database::new();
+        parse_source(&mut database, source).unwrap();
+        database
+    }
+
+    #[test]
+    fn symbol_cache_gives_the_same_answers() {
+        let source = "
+            module A { pub function f() {} pub function g() {} }
+            using A.f;
+            function one() { f(); f(); A.g(); missing(); }
+            function two() { f(); f(); A.g(); missing(); }
+            module B { function three() { f(); f(); crate.A.g(); } }
+        ";
+
+        let mut cached = parse(source);
+        let mut uncached = parse(source);
+        uncached.set_symbol_cache(false);
+
+        assert_eq!(cached.resolve_idents(), uncached.resolve_idents());
+        assert_eq!(cached.resolved_ast_pretty(), uncached.resolved_ast_pretty());
+        assert_eq!(cached.diagnostics().len(), 2);
+    }
+
+    #[test]
+    fn failed_path_keeps_the_resolved_prefix() {
+        let mut database = parse(
+            "module A { pub module inner {} }
+            function f() { A.inner.missing(); }",
+        );
+        let diagnostics = database.resolve_idents();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "resolved `A.inner` but `inner` has no child `missing`"
+        );
+
+        let f = database.lookup_path(&["f"]).unwrap();
+        let UnresolvedAST::Call { ident, .. } = &database.get_unresolved_body(f)[0];
+        let error = database.resolve_single_ident(f, ident).unwrap_err();
+        assert_eq!(error.matched, 2);
+        assert_eq!(error.last_good, database.lookup_path(&["A", "inner"]));
+    }
+
+    #[test]
+    fn item_kind_display() {
+        assert_eq!(format!("{}", ItemKind::Module), "module");
+        assert_eq!(format!("{}", ItemKind::Function), "function");
+        assert_eq!(format!("{:?}", ItemKind::Module), "Module");
+    }
 }
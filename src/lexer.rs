@@ -1,53 +1,365 @@
-use logos::Logos;
+use std::ops::Range;
+
+use logos::{FilterResult, Lexer, Logos};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Logos)]
 #[logos(skip "[ \t\r\n]*")]
+#[logos(extras = LexOptions)]
 pub enum TokenKind {
+    #[token("alias")]
+    Alias,
+
+    #[token("as")]
+    As,
+
     #[token("{")]
     BraceLeft,
 
     #[token("}")]
     BraceRight,
 
+    #[token("::")]
+    ColonColon,
+
+    #[token(",")]
+    Comma,
+
+    // Only produced when collecting trivia, otherwise comments are skipped. The parser ignores
+    // them either way.
+    #[regex(r"//[^\n]*", line_comment)]
+    #[token("/*", block_comment)]
+    Comment,
+
+    #[token("const")]
+    Const,
+
     #[token(".")]
     Dot,
 
-    #[regex("[a-zA-Z][a-zA-Z0-9_]+")]
+    #[token("entry")]
+    Entry,
+
+    #[token("=")]
+    Equals,
+
+    #[token("except")]
+    Except,
+
+    // The raw form lets keywords be used as names. Names can start with an underscore, but a lone
+    // `_` is its own token.
+    #[regex("[a-zA-Z_][a-zA-Z0-9_]*")]
+    #[regex("r#[a-zA-Z_][a-zA-Z0-9_]*")]
     Ident,
 
     #[token("function")]
     Function,
 
+    #[token("if")]
+    If,
+
     #[token("module")]
     Module,
 
+    // Never actually produced. Names can't start with a digit, and there are no numbers, so this
+    // just makes the error cover the whole would-be name rather than only its first digit.
+    #[regex("[0-9][a-zA-Z0-9_]*", numeric_name)]
+    NumericName,
+
     #[token("(")]
     ParenLeft,
 
     #[token(")")]
     ParenRight,
 
+    #[token("pub")]
+    Pub,
+
     #[token(";")]
     Semicolon,
 
+    #[token("#[skip]")]
+    Skip,
+
+    #[token("*")]
+    Star,
+
+    // Reserved as a wildcard, so never a name. Nothing accepts it yet.
+    #[token("_", priority = 3)]
+    Underscore,
+
     #[token("using")]
     Using,
 
     Eof,
 }
 
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LexOptions {
+    // Emit comments as `Comment` tokens, for tools that need to know where they were.
+    pub collect_trivia: bool,
+}
+
+fn comment_result(lexer: &Lexer<TokenKind>) -> FilterResult<(), ()> {
+    if lexer.extras.collect_trivia {
+        FilterResult::Emit(())
+    } else {
+        FilterResult::Skip
+    }
+}
+
+fn line_comment(lexer: &mut Lexer<TokenKind>) -> FilterResult<(), ()> {
+    comment_result(lexer)
+}
+
+fn block_comment(lexer: &mut Lexer<TokenKind>) -> FilterResult<(), ()> {
+    // Logos' regexes can't count, so we scan for nested comments by hand. The opening delimiter
+    // has already been consumed, so we start one level deep.
+    let bytes = lexer.remainder().as_bytes();
+    let mut depth = 1;
+    let mut i = 0;
+
+    while i + 1 < bytes.len() {
+        match &bytes[i..i + 2] {
+            b"/*" => {
+                depth += 1;
+                i += 2;
+            }
+            b"*/" => {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    lexer.bump(i);
+                    return comment_result(lexer);
+                }
+            }
+            _ => i += 1,
+        }
+    }
+
+    // Leave the span on the outermost opening delimiter, so the error points there.
+    FilterResult::Error(())
+}
+
+fn numeric_name(_: &mut Lexer<TokenKind>) -> FilterResult<(), ()> {
+    FilterResult::Error(())
+}
+
+// Whether a name is one an `Ident` token could have, once any `r#` is stripped.
+pub(crate) fn is_valid_ident(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && name != "_"
+}
+
 #[derive(Debug, Clone)]
 pub struct Token {
     pub kind: TokenKind,
     pub lexeme: String,
+    pub span: Range<usize>,
 }
 
-pub fn lex(source: &str) -> Vec<Token> {
-    TokenKind::lexer(source)
+#[derive(Debug, Clone)]
+pub struct LexError {
+    pub offset: usize,
+    pub slice: String,
+}
+
+pub fn lex(source: &str) -> Result<Vec<Token>, LexError> {
+    lex_with(source, LexOptions::default())
+}
+
+pub fn lex_with(source: &str, options: LexOptions) -> Result<Vec<Token>, LexError> {
+    lex_iter_with(source, options).collect()
+}
+
+// Lexes lazily, one token at a time.
+pub fn lex_iter(source: &str) -> impl Iterator<Item = Result<Token, LexError>> + '_ {
+    lex_iter_with(source, LexOptions::default())
+}
+
+pub fn lex_iter_with(
+    source: &str,
+    options: LexOptions,
+) -> impl Iterator<Item = Result<Token, LexError>> + '_ {
+    TokenKind::lexer_with_extras(source, options)
         .spanned()
-        .map(|(tk, span)| Token {
-            kind: tk.unwrap(),
-            lexeme: source[span].to_owned(),
+        .map(|(tk, span)| match tk {
+            Ok(kind) => {
+                // A raw ident's name doesn't include the `r#`.
+                let slice = &source[span.clone()];
+                let lexeme = match kind {
+                    TokenKind::Ident => slice.strip_prefix("r#").unwrap_or(slice),
+                    _ => slice,
+                };
+
+                Ok(Token {
+                    kind,
+                    lexeme: lexeme.to_owned(),
+                    span,
+                })
+            }
+            Err(()) => Err(LexError {
+                offset: span.start,
+                slice: source[span].to_owned(),
+            }),
         })
-        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        lex(source).unwrap().iter().map(|t| t.kind).collect()
+    }
+
+    #[test]
+    fn single_character_idents() {
+        for source in ["a", "x1"] {
+            let tokens = lex(source).unwrap();
+            assert_eq!(tokens.len(), 1);
+            assert_eq!(tokens[0].kind, TokenKind::Ident);
+            assert_eq!(tokens[0].lexeme, source);
+        }
+    }
+
+    #[test]
+    fn keywords_beat_idents() {
+        assert_eq!(
+            kinds("function module using"),
+            [TokenKind::Function, TokenKind::Module, TokenKind::Using]
+        );
+    }
+
+    #[test]
+    fn token_spans() {
+        let tokens = lex("module A1 {}").unwrap();
+        assert_eq!(tokens[1].lexeme, "A1");
+        assert_eq!(tokens[1].span, 7..9);
+    }
+
+    #[test]
+    fn unrecognized_input() {
+        let error = lex("module A1 { @ }").unwrap_err();
+        assert_eq!(error.offset, 12);
+        assert_eq!(error.slice, "@");
+    }
+
+    #[test]
+    fn line_comments() {
+        let tokens = lex("module A {} // trailing\nmodule B {}").unwrap();
+        assert_eq!(tokens.len(), 8);
+        assert_eq!(tokens[5].lexeme, "B");
+        assert_eq!(tokens[5].span, 31..32);
+
+        assert_eq!(
+            kinds("function f() {}\n// between\nfunction g() {}").len(),
+            12
+        );
+
+        // There's no Eof token, so a file that's only a comment has nothing in it.
+        assert!(kinds("// nothing but a comment").is_empty());
+
+        let tokens = lex("abc//x").unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].lexeme, "abc");
+    }
+
+    #[test]
+    fn block_comments() {
+        let tokens = lex("function f() {}\n/* one\ntwo\n*/\nfunction g() {}").unwrap();
+        assert_eq!(tokens.len(), 12);
+        assert_eq!(tokens[7].lexeme, "g");
+        assert_eq!(tokens[7].span, 39..40);
+
+        let error = lex("module A {} /* never closed\n").unwrap_err();
+        assert_eq!(error.offset, 12);
+    }
+
+    #[test]
+    fn nested_block_comments() {
+        assert_eq!(
+            kinds("/* outer /* inner */ still outer */ module A {}"),
+            [
+                TokenKind::Module,
+                TokenKind::Ident,
+                TokenKind::BraceLeft,
+                TokenKind::BraceRight
+            ]
+        );
+
+        // The inner comment is closed, but the outer one isn't.
+        let error = lex("module A {}\n/* outer /* inner */ still outer").unwrap_err();
+        assert_eq!(error.offset, 12);
+    }
+
+    #[test]
+    fn raw_identifiers() {
+        let tokens = lex("r#using").unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].kind, TokenKind::Ident);
+        assert_eq!(tokens[0].lexeme, "using");
+        assert_eq!(tokens[0].span, 0..7);
+    }
+
+    #[test]
+    fn streaming_matches_eager() {
+        let source = "module A {\n    // comment\n    pub function f(x) { B.g(x); }\n}\n/* block */ using A.*;";
+
+        let eager = lex(source).unwrap();
+        let streamed: Vec<_> = lex_iter(source).map(Result::unwrap).collect();
+        assert_eq!(eager.len(), streamed.len());
+        for (a, b) in eager.iter().zip(&streamed) {
+            assert_eq!((a.kind, &a.lexeme, &a.span), (b.kind, &b.lexeme, &b.span));
+        }
+    }
+
+    #[test]
+    fn underscore_idents() {
+        let tokens = lex("_x __ _").unwrap();
+        assert_eq!(tokens[0].kind, TokenKind::Ident);
+        assert_eq!(tokens[0].lexeme, "_x");
+        assert_eq!(tokens[1].kind, TokenKind::Ident);
+        assert_eq!(tokens[1].lexeme, "__");
+        // A lone underscore is reserved, not a name.
+        assert_eq!(tokens[2].kind, TokenKind::Underscore);
+        assert!(!is_valid_ident("_"));
+    }
+
+    #[test]
+    fn digits_in_names() {
+        let tokens = lex("A1B2C3 x9").unwrap();
+        assert_eq!(tokens[0].lexeme, "A1B2C3");
+        assert_eq!(tokens[1].lexeme, "x9");
+        assert!(tokens.iter().all(|t| t.kind == TokenKind::Ident));
+
+        // The error covers the whole name, not just the digit.
+        let error = lex("module 1A {}").unwrap_err();
+        assert_eq!(error.offset, 7);
+        assert_eq!(error.slice, "1A");
+    }
+
+    #[test]
+    fn comments_as_trivia() {
+        let source = "module A {} // line\n/* block */ module B {}";
+        let options = LexOptions {
+            collect_trivia: true,
+        };
+        let tokens = lex_with(source, options).unwrap();
+        let comments: Vec<_> = tokens
+            .iter()
+            .filter(|t| t.kind == TokenKind::Comment)
+            .map(|t| (t.lexeme.as_str(), t.span.clone()))
+            .collect();
+        assert_eq!(comments, [("// line", 12..19), ("/* block */", 20..31)]);
+
+        // They're dropped by default, and the parser skips them if they're there.
+        assert!(!kinds(source).contains(&TokenKind::Comment));
+        let mut database = crate::Database::new();
+        crate::parse(&mut database, &tokens).unwrap();
+        assert!(database.lookup_path(&["B"]).is_some());
+    }
 }
@@ -0,0 +1,41 @@
+pub mod ast;
+pub mod builder;
+pub mod database;
+pub mod lexer;
+pub mod line_index;
+pub mod parser;
+
+pub use ast::{ItemTree, ResolvedVisitor};
+pub use builder::DatabaseBuilder;
+pub use database::{ChildOrder, Database, Diagnostic, Severity};
+pub use lexer::{lex, lex_iter, lex_iter_with, lex_with, LexOptions};
+pub use line_index::LineIndex;
+pub use parser::{parse, parse_source, parse_to_items, ParseError};
+
+pub fn resolve_str(source: &str) -> Result<Database, Vec<Diagnostic>> {
+    let mut database = Database::new();
+
+    if let Err(errors) = parse_source(&mut database, source) {
+        return Err(errors
+            .iter()
+            .map(|e| parse_diagnostic(&database, e))
+            .collect());
+    }
+
+    // Warnings alone don't stop us.
+    let diagnostics = database.resolve_idents();
+    if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        return Err(diagnostics);
+    }
+
+    Ok(database)
+}
+
+fn parse_diagnostic(database: &Database, error: &ParseError) -> Diagnostic {
+    Diagnostic {
+        message: error.message.clone(),
+        item: database.root(),
+        severity: Severity::Error,
+        span: error.span.clone(),
+    }
+}
@@ -0,0 +1,82 @@
+use std::ops::Range;
+
+// Converts byte offsets in a source into 1-based line and column numbers. The line starts are
+// found once up front, so each lookup is just a binary search. Columns count characters, not bytes.
+pub struct LineIndex<'a> {
+    source: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        Self {
+            source,
+            line_starts,
+        }
+    }
+
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_of(offset);
+        let line_start = self.line_starts[line];
+        let column = self.source[line_start..offset].chars().count() + 1;
+
+        (line + 1, column)
+    }
+
+    // The text of the 1-based line, without its line ending, whether that's `\n` or `\r\n`.
+    pub fn line_span(&self, line: usize) -> Range<usize> {
+        let start = self.line_starts[line - 1];
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.source.len(), |&next| next - 1);
+        let text = self.source[start..end].trim_end_matches('\r');
+
+        start..start + text.len()
+    }
+
+    fn line_of(&self, offset: usize) -> usize {
+        // An offset on a line start is found exactly, anything else lands just after its line's
+        // start.
+        match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_col() {
+        let index = LineIndex::new("ab\ncd\r\nef");
+        assert_eq!(index.line_col(0), (1, 1));
+        assert_eq!(index.line_col(2), (1, 3));
+        // Just after a newline is the start of the next line.
+        assert_eq!(index.line_col(3), (2, 1));
+        assert_eq!(index.line_col(7), (3, 1));
+        // The end of the source is still a position, after the last character.
+        assert_eq!(index.line_col(9), (3, 3));
+    }
+
+    #[test]
+    fn columns_count_characters() {
+        let index = LineIndex::new("é = x");
+        assert_eq!(index.line_col(2), (1, 2));
+    }
+
+    #[test]
+    fn line_span_drops_the_line_ending() {
+        let source = "ab\ncd\r\nef";
+        let index = LineIndex::new(source);
+        assert_eq!(&source[index.line_span(1)], "ab");
+        assert_eq!(&source[index.line_span(2)], "cd");
+        assert_eq!(&source[index.line_span(3)], "ef");
+    }
+}
@@ -1,22 +1,200 @@
-mod ast;
-mod database;
-mod lexer;
-mod parser;
+use std::io::{BufRead, Write};
 
-use database::Database;
+use simple_ident_res::{Database, Diagnostic};
 
 fn main() {
-    let contents = std::fs::read_to_string("example.foo").unwrap();
-    let tokens = lexer::lex(&contents);
+    if std::env::args().skip(1).any(|arg| arg == "--repl") {
+        let stdin = std::io::stdin();
+        repl(stdin.lock(), &mut std::io::stdout());
+        return;
+    }
 
+    let path = input_path(std::env::args().skip(1));
+    let contents = match read_input(&path) {
+        Ok(contents) => contents,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+
+    match simple_ident_res::resolve_str(&contents) {
+        Ok(database) => {
+            for diag in database.diagnostics() {
+                eprint!("{}", Database::render_diagnostic(&contents, diag));
+            }
+
+            database.print_headers();
+            database.print_unresolved_ast();
+            database.print_resolved_ast();
+        }
+        Err(diagnostics) => {
+            for diag in &diagnostics {
+                eprint!("{}", Database::render_diagnostic(&contents, diag));
+            }
+        }
+    }
+}
+
+// The first argument that isn't a flag, if there is one.
+fn input_path(mut args: impl Iterator<Item = String>) -> String {
+    args.find(|arg| !arg.starts_with("--"))
+        .unwrap_or_else(|| "example.foo".to_owned())
+}
+
+fn read_input(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path)
+        .map_err(|error| format!("error: could not read `{}`: {}", path, error))
+}
+
+fn format_diagnostic(database: &Database, diag: &Diagnostic) -> String {
+    format!(
+        "{} in {}: {}",
+        diag.severity,
+        database.display_path(diag.item),
+        diag.message
+    )
+}
+
+// The function a statement is wrapped in, so it can be resolved like any other body.
+const REPL_FUNCTION: &str = "_repl";
+
+// Each line has to be complete on its own. A line of items is added to the program, which is then
+// re-resolved, and any new diagnostics are shown. Anything else is taken as a statement, which is
+// resolved against the program so far and then thrown away.
+fn repl(input: impl BufRead, out: &mut impl Write) {
     let mut database = Database::new();
+    let mut previous = Vec::new();
+
+    for line in input.lines() {
+        let Ok(line) = line else { break };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut items = Database::new();
+        let item_errors = match simple_ident_res::parse_source(&mut items, &line) {
+            Ok(()) => {
+                let root = database.root();
+                database.merge(items, root);
+                let diagnostics = database.resolve_idents();
+                for diag in diagnostics.iter().filter(|d| !previous.contains(*d)) {
+                    let _ = writeln!(out, "{}", format_diagnostic(&database, diag));
+                }
+                previous = diagnostics;
+                continue;
+            }
+            Err(errors) => errors,
+        };
+
+        let wrapped = format!("function {}() {{ {} }}", REPL_FUNCTION, line);
+        let mut statement = Database::new();
+        if simple_ident_res::parse_source(&mut statement, &wrapped).is_err() {
+            // The line wasn't a statement either, so it was most likely meant as items.
+            for error in item_errors {
+                let _ = writeln!(out, "error: {}", error.message);
+            }
+            continue;
+        }
+
+        let root = database.root();
+        database.merge(statement, root);
+        let Some(func) = database.lookup_path(&[REPL_FUNCTION]) else {
+            continue;
+        };
+
+        let diagnostics = database.resolve_idents();
+        for diag in diagnostics.iter().filter(|d| d.item == func) {
+            let _ = writeln!(out, "{}", format_diagnostic(&database, diag));
+        }
+
+        let prefix = format!("    {} ", REPL_FUNCTION);
+        for call in database.resolved_ast_pretty().lines() {
+            if let Some(call) = call.strip_prefix(&prefix) {
+                let _ = writeln!(out, "{}", call);
+            }
+        }
+
+        database.remove_item(func);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args<'a>(args: &'a [&str]) -> impl Iterator<Item = String> + 'a {
+        args.iter().map(|&a| a.to_owned())
+    }
+
+    #[test]
+    fn input_path_from_args() {
+        assert_eq!(input_path(args(&["src.foo"])), "src.foo");
+        // Flags are skipped wherever they are.
+        assert_eq!(
+            input_path(args(&["--repl", "src.foo", "other.foo"])),
+            "src.foo"
+        );
+    }
+
+    #[test]
+    fn input_path_defaults_to_the_example() {
+        assert_eq!(input_path(args(&[])), "example.foo");
+        assert_eq!(input_path(args(&["--repl"])), "example.foo");
+    }
+
+    #[test]
+    fn missing_input_file() {
+        let message = read_input("does/not/exist.foo").unwrap_err();
+        assert!(message.starts_with("error: could not read `does/not/exist.foo`: "));
+    }
+
+    #[test]
+    fn repl_lines() {
+        let input = "module A { pub function f() {} }
+A.f();
+
+missing();
+function g() { nope(); }
+module {
+";
+        let mut out = Vec::new();
+        repl(input.as_bytes(), &mut out);
+
+        // Statements are reported against their wrapper function.
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "-> A.f
+error in _repl: symbol `missing` not found
+-> <unresolved: missing>
+error in g: symbol `nope` not found
+error: expected Ident, found BraceLeft
+"
+        );
+    }
 
-    parser::parse(&mut database, &tokens);
+    #[test]
+    fn repl_only_shows_new_diagnostics() {
+        let input = "function g() { nope(); }\nfunction h() {}\nfunction nope() {}\ng();\n";
+        let mut out = Vec::new();
+        repl(input.as_bytes(), &mut out);
 
-    database.print_headers();
-    database.print_unresolved_ast();
+        // Declaring `nope` fixes `g`, so nothing more is said about it.
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "error in g: symbol `nope` not found\n-> g\n"
+        );
+    }
 
-    database.resolve_idents();
+    #[test]
+    fn repl_names_nested_and_root_items() {
+        let input = "module A { function f() { nope(); } }\nusing missing;\n";
+        let mut out = Vec::new();
+        repl(input.as_bytes(), &mut out);
 
-    database.print_resolved_ast();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "error in A.f: symbol `nope` not found\nerror in <ROOT>: symbol `missing` not found\n"
+        );
+    }
 }
@@ -1,135 +1,936 @@
-use std::slice::Iter;
+use std::{collections::VecDeque, ops::Range};
 
 use crate::{
-    ast::{UnresolvedAST, UnresolvedIdent},
-    database::{Database, ItemId, ItemKind},
-    lexer::{Token, TokenKind},
+    ast::{ItemTree, UnresolvedAST, UnresolvedChainLink, UnresolvedIdent, UnresolvedImport},
+    database::{Database, ItemId, ItemKind, Visibility},
+    lexer::{is_valid_ident, lex_iter, LexError, Token, TokenKind},
 };
 
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Range<usize>,
+}
+
+impl From<LexError> for ParseError {
+    fn from(e: LexError) -> Self {
+        Self {
+            message: format!("unrecognized input `{}`", e.slice),
+            span: e.offset..e.offset + e.slice.len(),
+        }
+    }
+}
+
 struct Parser<'a> {
-    token_iter: Iter<'a, Token>,
+    // Tokens are pulled from the lexer as they're needed, with the ones peeked at but not yet
+    // taken kept in the lookahead.
+    tokens: Box<dyn Iterator<Item = Result<Token, LexError>> + 'a>,
+    lookahead: VecDeque<Token>,
+    // Where an Eof "token" would be, just past the end of the last real one.
+    eof_span: Range<usize>,
+    // The end of the last token taken by `expect`.
+    prev_end: usize,
+    errors: Vec<ParseError>,
+    // The lexer is done once it fails, and anything we'd say about the input after that is noise.
+    lex_failed: bool,
 }
 
-impl Parser<'_> {
-    fn expect(&mut self, kind: TokenKind) -> &Token {
+impl<'a> Parser<'a> {
+    fn new(tokens: impl Iterator<Item = Result<Token, LexError>> + 'a) -> Self {
+        Self {
+            tokens: Box::new(tokens),
+            lookahead: VecDeque::new(),
+            eof_span: 0..0,
+            prev_end: 0,
+            errors: Vec::new(),
+            lex_failed: false,
+        }
+    }
+
+    fn fill(&mut self, n: usize) {
+        while self.lookahead.len() <= n && !self.lex_failed {
+            match self.tokens.next() {
+                // Comments are only there for tools that want them, and never affect the parse.
+                Some(Ok(token)) if token.kind == TokenKind::Comment => {}
+                Some(Ok(token)) => {
+                    self.eof_span = token.span.end..token.span.end;
+                    self.lookahead.push_back(token);
+                }
+                Some(Err(e)) => {
+                    self.errors.push(e.into());
+                    self.lex_failed = true;
+                }
+                None => break,
+            }
+        }
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        self.fill(0);
+        self.lookahead.pop_front()
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<Token, ParseError> {
         if self.peek() == kind {
-            return self.token_iter.next().unwrap();
+            let token = self.next().unwrap();
+            self.prev_end = token.span.end;
+            return Ok(token);
+        }
+
+        Err(self.unexpected(&format!("{:?}", kind)))
+    }
+
+    fn expect_semicolon(&mut self) {
+        // A missing semicolon is easy to recover from, so rather than skipping to the next one and
+        // taking the following statement with it, we report it and carry on as if it were there.
+        if self.peek() == TokenKind::Semicolon {
+            self.next();
+            return;
+        }
+
+        let error = ParseError {
+            message: format!("expected Semicolon, found {:?}", self.peek()),
+            span: self.prev_end..self.prev_end,
+        };
+        self.report(error);
+    }
+
+    fn expect_closing(&mut self, kind: TokenKind, open: Range<usize>) -> Result<(), ParseError> {
+        // Running out of tokens is most likely a delimiter that was never closed, so point at that
+        // rather than the end of the file.
+        if self.peek() == TokenKind::Eof {
+            return Err(ParseError {
+                message: format!(
+                    "expected {:?}, found Eof; this delimiter is never closed",
+                    kind
+                ),
+                span: open,
+            });
         }
 
-        panic!("Expected token {:?}, found {:?}", kind, self.peek());
+        self.expect(kind)?;
+        Ok(())
     }
 
-    fn peek(&self) -> TokenKind {
-        self.token_iter
-            .clone()
-            .next()
-            .map(|t| t.kind)
-            .unwrap_or(TokenKind::Eof)
+    fn peek(&mut self) -> TokenKind {
+        self.peek_nth(0)
     }
+
+    fn peek_nth(&mut self, n: usize) -> TokenKind {
+        self.fill(n);
+        self.lookahead.get(n).map_or(TokenKind::Eof, |t| t.kind)
+    }
+
+    fn peek_lexeme(&mut self) -> &str {
+        self.fill(0);
+        self.lookahead.front().map_or("", |t| t.lexeme.as_str())
+    }
+
+    fn peek_span(&mut self) -> Range<usize> {
+        self.fill(0);
+        self.lookahead
+            .front()
+            .map_or(self.eof_span.clone(), |t| t.span.clone())
+    }
+
+    fn unexpected(&mut self, expected: &str) -> ParseError {
+        ParseError {
+            message: format!("expected {}, found {:?}", expected, self.peek()),
+            span: self.peek_span(),
+        }
+    }
+
+    fn report(&mut self, error: ParseError) {
+        // A missing closing brace at the end of the file would otherwise be reported once for
+        // every enclosing block.
+        if self.lex_failed || self.errors.last().is_some_and(|e| e.span == error.span) {
+            return;
+        }
+
+        self.errors.push(error);
+    }
+
+    fn recover(&mut self) {
+        // Skip to what's probably the end of the broken statement or item: a semicolon, or the end
+        // of a block opened while skipping, like a broken function's body. Any other closing brace
+        // is left for the enclosing block to handle.
+        let mut depth = 0;
+        loop {
+            match self.peek() {
+                TokenKind::Semicolon if depth == 0 => {
+                    self.next();
+                    break;
+                }
+                TokenKind::BraceLeft => {
+                    depth += 1;
+                    self.next();
+                }
+                TokenKind::BraceRight if depth > 0 => {
+                    depth -= 1;
+                    self.next();
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                TokenKind::BraceRight | TokenKind::Eof => break,
+                _ => {
+                    self.next();
+                }
+            }
+        }
+    }
+
+    fn finish(self) -> Result<(), Vec<ParseError>> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+}
+
+// The database is filled with everything that could be parsed, even if there were errors.
+pub fn parse(database: &mut Database, tokens: &[Token]) -> Result<(), Vec<ParseError>> {
+    load_root(database, Parser::new(tokens.iter().cloned().map(Ok)))
+}
+
+// Same as `parse`, but lexes the source as it goes rather than needing all the tokens up front.
+pub fn parse_source(database: &mut Database, source: &str) -> Result<(), Vec<ParseError>> {
+    load_root(database, Parser::new(lex_iter(source)))
+}
+
+// Parses into a plain item tree rather than a database, which can be loaded into one later with
+// `Database::load_items`. The tree is the file's top level, so it holds the root's imports and
+// any `crate` name as well as the items.
+pub fn parse_to_items(tokens: &[Token]) -> Result<ItemTree, Vec<ParseError>> {
+    let mut parser = Parser::new(tokens.iter().cloned().map(Ok));
+    let root = parse_root(&mut parser);
+    parser.finish()?;
+    Ok(root)
 }
 
-pub(crate) fn parse(database: &mut Database, tokens: &[Token]) {
-    let mut parser = Parser {
-        token_iter: tokens.iter(),
-    };
+fn load_root(database: &mut Database, mut parser: Parser) -> Result<(), Vec<ParseError>> {
+    let tree = parse_root(&mut parser);
+    let root = database.root();
+    database.load_items(root, tree);
+    parser.finish()
+}
 
-    // Parsing top-level modules.
+fn parse_root(parser: &mut Parser) -> ItemTree {
+    // The top level is treated as the root module's block.
+    let mut root = ItemTree::top_level();
     loop {
+        parse_module_items(parser, &mut root, true);
         if parser.peek() == TokenKind::Eof {
             break;
         }
-        parser.expect(TokenKind::Module);
-        parse_module(database, &mut parser, None);
+
+        // There's no enclosing block for a stray closing brace to belong to. Item parsing only
+        // stops at one of those or the end, so this is always the brace.
+        let e = parser.unexpected("an item");
+        parser.report(e);
+        parser.expect(TokenKind::BraceRight).unwrap();
+    }
+
+    root
+}
+
+fn parse_visibility(parser: &mut Parser) -> Result<Visibility, ParseError> {
+    if parser.peek() == TokenKind::Pub {
+        parser.expect(TokenKind::Pub)?;
+        Ok(Visibility::Public)
+    } else {
+        Ok(Visibility::Private)
     }
 }
 
-fn parse_module(database: &mut Database, parser: &mut Parser, parent_id: Option<ItemId>) {
+// Parses an optional `#[skip]` and the visibility after it, which must be followed by one of the
+// given item keywords if the item is skipped.
+fn parse_skip_and_visibility(
+    parser: &mut Parser,
+    allowed: &[TokenKind],
+) -> Result<(bool, Visibility), ParseError> {
+    if parser.peek() != TokenKind::Skip {
+        return Ok((false, parse_visibility(parser)?));
+    }
+
+    let span = parser.expect(TokenKind::Skip)?.span;
+    let visibility = parse_visibility(parser)?;
+    if !allowed.contains(&parser.peek()) {
+        return Err(ParseError {
+            message: "`#[skip]` can only be used on a function or module".to_owned(),
+            span,
+        });
+    }
+
+    Ok((true, visibility))
+}
+
+fn parse_module(
+    parser: &mut Parser,
+    parent: &mut ItemTree,
+    visibility: Visibility,
+    skip: bool,
+) -> Result<(), ParseError> {
     // Keyword is already parsed
-    let name = parser.expect(TokenKind::Ident).lexeme.clone();
-    let module_id = database.new_item(name, ItemKind::Module, parent_id);
+    let name = parser.expect(TokenKind::Ident)?;
+    let mut module = ItemTree::new(name.lexeme, name.span, ItemKind::Module, visibility);
+    module.skipped = skip;
 
-    parse_module_block(database, parser, module_id);
+    // The module is kept even if its block is broken, with whatever could be parsed of it.
+    let result = parse_module_block(parser, &mut module, false);
+    parent.children.push(module);
+    result
 }
 
-fn parse_module_block(database: &mut Database, parser: &mut Parser, parent_id: ItemId) {
-    parser.expect(TokenKind::BraceLeft);
+// Parses the contents of a file straight into an existing module, as if it were that module's block.
+pub fn parse_into_module(
+    database: &mut Database,
+    tokens: &[Token],
+    module_id: ItemId,
+) -> Result<(), Vec<ParseError>> {
+    let mut parser = Parser::new(tokens.iter().cloned().map(Ok));
 
-    loop {
-        match parser.peek() {
-            TokenKind::Function => {
-                parser.expect(TokenKind::Function);
-                parse_function(database, parser, parent_id);
-            }
-            TokenKind::Module => {
-                parser.expect(TokenKind::Module);
-                parse_module(database, parser, Some(parent_id));
-            }
-            TokenKind::Using => {
-                parser.expect(TokenKind::Using);
-                parse_using(database, parser, parent_id);
+    let mut tree = ItemTree::top_level();
+    parse_module_items(&mut parser, &mut tree, module_id == database.root());
+    if parser.peek() != TokenKind::Eof {
+        let e = parser.unexpected("Eof");
+        parser.report(e);
+    }
+
+    database.load_items(module_id, tree);
+    parser.finish()
+}
+
+fn parse_module_block(
+    parser: &mut Parser,
+    module: &mut ItemTree,
+    top_level: bool,
+) -> Result<(), ParseError> {
+    let open = parser.expect(TokenKind::BraceLeft)?.span.clone();
+    parse_module_items(parser, module, top_level);
+    parser.expect_closing(TokenKind::BraceRight, open)
+}
+
+fn parse_module_items(parser: &mut Parser, module: &mut ItemTree, top_level: bool) {
+    // The caller checks which of these we should have stopped at.
+    while !matches!(parser.peek(), TokenKind::BraceRight | TokenKind::Eof) {
+        if let Err(e) = parse_module_item(parser, module, top_level) {
+            parser.report(e);
+            parser.recover();
+        }
+    }
+}
+
+fn parse_module_item(
+    parser: &mut Parser,
+    module: &mut ItemTree,
+    top_level: bool,
+) -> Result<(), ParseError> {
+    // A skipped item is still parsed in full, it just doesn't take part in resolution.
+    let (skip, visibility) = parse_skip_and_visibility(
+        parser,
+        &[TokenKind::Entry, TokenKind::Function, TokenKind::Module],
+    )?;
+    match parser.peek() {
+        TokenKind::Entry => {
+            let span = parser.expect(TokenKind::Entry)?.span;
+            if parser.peek() != TokenKind::Function {
+                return Err(ParseError {
+                    message: "`entry` can only be used on a function".to_owned(),
+                    span,
+                });
             }
-            TokenKind::BraceRight => break,
-            t => panic!("{:?}", t),
+            parser.expect(TokenKind::Function)?;
+            parse_function(parser, module, visibility, skip, true)
+        }
+        TokenKind::Function => {
+            parser.expect(TokenKind::Function)?;
+            parse_function(parser, module, visibility, skip, false)
         }
+        TokenKind::Module => {
+            parser.expect(TokenKind::Module)?;
+            parse_module(parser, module, visibility, skip)
+        }
+        TokenKind::Const => {
+            parser.expect(TokenKind::Const)?;
+            parse_const(parser, module, visibility)
+        }
+        TokenKind::Using => {
+            parser.expect(TokenKind::Using)?;
+            parse_using(parser, &mut module.imports, visibility)
+        }
+        TokenKind::Alias => {
+            parser.expect(TokenKind::Alias)?;
+            parse_alias(parser, module, visibility)
+        }
+        // `crate` isn't a keyword, as it's also the start of a path.
+        TokenKind::Ident
+            if top_level
+                && visibility == Visibility::Private
+                && !skip
+                && parser.peek_lexeme() == "crate" =>
+        {
+            parser.expect(TokenKind::Ident)?;
+            parse_crate(parser, module)
+        }
+        _ => Err(parser.unexpected("an item")),
     }
+}
 
-    parser.expect(TokenKind::BraceRight);
+fn parse_crate(parser: &mut Parser, root: &mut ItemTree) -> Result<(), ParseError> {
+    // Keyword is already parsed. The wrapper's contents are the root's, it just gives it a name.
+    // A second wrapper is still parsed, so its contents are checked too.
+    let name = parser.expect(TokenKind::Ident)?;
+    if root.is_named_crate() {
+        parser.report(ParseError {
+            message: format!(
+                "crate `{}` is already named, it can't also be `{}`",
+                root.name, name.lexeme
+            ),
+            span: name.span,
+        });
+    } else {
+        root.name = name.lexeme;
+        root.span = name.span;
+    }
+
+    parse_module_block(parser, root, true)
 }
 
-fn parse_using(database: &mut Database, parser: &mut Parser, item_id: ItemId) {
+fn parse_const(
+    parser: &mut Parser,
+    parent: &mut ItemTree,
+    visibility: Visibility,
+) -> Result<(), ParseError> {
     // Keyword is already parsed.
-    let ident = parse_ident(parser);
-    parser.expect(TokenKind::Semicolon);
-    database.add_import(item_id, ident);
+    let name = parser.expect(TokenKind::Ident)?;
+    parser.expect_semicolon();
+    parent.children.push(ItemTree::new(
+        name.lexeme,
+        name.span,
+        ItemKind::Value,
+        visibility,
+    ));
+
+    Ok(())
 }
 
-fn parse_function(database: &mut Database, parser: &mut Parser, parent_id: ItemId) {
+fn parse_alias(
+    parser: &mut Parser,
+    parent: &mut ItemTree,
+    visibility: Visibility,
+) -> Result<(), ParseError> {
     // Keyword is already parsed.
-    let name = parser.expect(TokenKind::Ident).lexeme.clone();
-    let func_id = database.new_item(name, ItemKind::Function, Some(parent_id));
+    let name = parser.expect(TokenKind::Ident)?;
+    parser.expect(TokenKind::Equals)?;
+    let target = parse_ident(parser)?;
+    parser.expect_semicolon();
 
-    parser.expect(TokenKind::ParenLeft);
-    parser.expect(TokenKind::ParenRight);
+    let mut alias = ItemTree::new(name.lexeme, name.span, ItemKind::Alias, visibility);
+    alias.alias_target = Some(target);
+    parent.children.push(alias);
 
-    parse_function_block(database, parser, func_id);
+    Ok(())
 }
 
-fn parse_function_block(database: &mut Database, parser: &mut Parser, func_id: ItemId) {
-    parser.expect(TokenKind::BraceLeft);
+fn parse_using(
+    parser: &mut Parser,
+    imports: &mut Vec<UnresolvedImport>,
+    visibility: Visibility,
+) -> Result<(), ParseError> {
+    // Keyword is already parsed.
+    let mut separator = None;
+    let prefix = parse_path(parser, &mut separator)?;
 
-    let mut ast = Vec::new();
+    let mut parsed = Vec::new();
+    if matches!(parser.peek(), TokenKind::Dot | TokenKind::ColonColon) {
+        parse_separator(parser, &mut separator)?;
+        if parser.peek() == TokenKind::BraceLeft {
+            // `using A.{b, c as d};` is the same as importing each of them on their own. Unlike
+            // other lists, a trailing comma is fine here. Groups can't be nested.
+            let open = parser.expect(TokenKind::BraceLeft)?.span.clone();
+            while parser.peek() != TokenKind::BraceRight {
+                let suffix = parse_path(parser, &mut separator)?;
+                let mut path = prefix.clone();
+                path.parts.extend(suffix.parts);
+                path.span.end = suffix.span.end;
 
-    loop {
-        match parser.peek() {
-            TokenKind::Ident => {
-                // We're just assuming these are all calls.
-                let ident = parse_ident(parser);
-                parser.expect(TokenKind::ParenLeft);
-                parser.expect(TokenKind::ParenRight);
-                parser.expect(TokenKind::Semicolon);
-                ast.push(UnresolvedAST::Call { ident });
+                let alias = parse_using_alias(parser)?;
+                parsed.push((path, alias, Vec::new(), false));
+
+                if parser.peek() != TokenKind::Comma {
+                    break;
+                }
+                parser.expect(TokenKind::Comma)?;
+            }
+            parser.expect_closing(TokenKind::BraceRight, open.clone())?;
+
+            // It would import nothing, which is most likely a mistake.
+            if parsed.is_empty() {
+                return Err(ParseError {
+                    message: format!("empty import group in `using {}`", prefix),
+                    span: open.start..parser.prev_end,
+                });
+            }
+        } else {
+            // A glob brings in everything, so there's nothing to alias, but some names can be
+            // left out.
+            parser.expect(TokenKind::Star)?;
+            let mut except = Vec::new();
+            if parser.peek() == TokenKind::Except {
+                parser.expect(TokenKind::Except)?;
+                except.push(parse_ident_part(parser)?);
+                while parser.peek() == TokenKind::Comma {
+                    parser.expect(TokenKind::Comma)?;
+                    except.push(parse_ident_part(parser)?);
+                }
+            }
+            parsed.push((prefix, None, except, true));
+        }
+    } else {
+        let alias = parse_using_alias(parser)?;
+        parsed.push((prefix, alias, Vec::new(), false));
+    }
+
+    // The guard applies to everything the statement imports.
+    let mut feature = None;
+    if parser.peek() == TokenKind::If {
+        parser.expect(TokenKind::If)?;
+        feature = Some(parse_ident_part(parser)?);
+    }
+
+    parser.expect_semicolon();
+    for (path, alias, except, glob) in parsed {
+        imports.push(UnresolvedImport {
+            path,
+            alias,
+            glob,
+            except,
+            visibility,
+            feature: feature.clone(),
+        });
+    }
+
+    Ok(())
+}
+
+fn parse_using_alias(parser: &mut Parser) -> Result<Option<String>, ParseError> {
+    if parser.peek() != TokenKind::As {
+        return Ok(None);
+    }
+
+    parser.expect(TokenKind::As)?;
+    Ok(Some(parser.expect(TokenKind::Ident)?.lexeme.clone()))
+}
+
+fn parse_function(
+    parser: &mut Parser,
+    parent: &mut ItemTree,
+    visibility: Visibility,
+    skip: bool,
+    entry: bool,
+) -> Result<(), ParseError> {
+    // Keyword is already parsed.
+    let name = parser.expect(TokenKind::Ident)?;
+    let mut function = ItemTree::new(name.lexeme, name.span, ItemKind::Function, visibility);
+    function.skipped = skip;
+    function.entry = entry;
+
+    // As with modules, the function is kept even if the rest of it is broken.
+    let result = parse_function_rest(parser, &mut function);
+    parent.children.push(function);
+    result
+}
+
+fn parse_function_rest(parser: &mut Parser, function: &mut ItemTree) -> Result<(), ParseError> {
+    function.params = parse_paren_list(parser, |p| Ok(p.expect(TokenKind::Ident)?.lexeme.clone()))?;
+
+    let open = parser.expect(TokenKind::BraceLeft)?.span.clone();
+
+    // Nested functions count as statements, so this isn't the same as the body being empty.
+    let is_empty = parser.peek() == TokenKind::BraceRight;
+
+    while !matches!(parser.peek(), TokenKind::BraceRight | TokenKind::Eof) {
+        if let Err(e) = parse_statement(parser, function) {
+            parser.report(e);
+            parser.recover();
+        }
+    }
+
+    parser.expect_closing(TokenKind::BraceRight, open)?;
+    function.empty = is_empty;
+
+    Ok(())
+}
+
+fn parse_statement(parser: &mut Parser, function: &mut ItemTree) -> Result<(), ParseError> {
+    match parser.peek() {
+        TokenKind::Ident => {
+            // We're just assuming these are all calls.
+            let ident = parse_ident(parser)?;
+            let args = parse_paren_list(parser, parse_ident)?;
+
+            // Each link in a chain is a single name, looked up in whatever the last call was.
+            let mut chain = Vec::new();
+            while parser.peek() == TokenKind::Dot {
+                parser.expect(TokenKind::Dot)?;
+                let part = parse_ident_part(parser)?;
+                let ident = UnresolvedIdent {
+                    span: part.1.clone(),
+                    parts: vec![part],
+                };
+                let args = parse_paren_list(parser, parse_ident)?;
+                chain.push(UnresolvedChainLink { ident, args });
             }
-            TokenKind::Using => {
-                parser.expect(TokenKind::Using);
-                parse_using(database, parser, func_id);
+
+            parser.expect_semicolon();
+            function
+                .body
+                .push(UnresolvedAST::Call { ident, args, chain });
+            Ok(())
+        }
+        TokenKind::Pub | TokenKind::Function | TokenKind::Skip => {
+            let (skip, visibility) = parse_skip_and_visibility(parser, &[TokenKind::Function])?;
+            parser.expect(TokenKind::Function)?;
+            parse_function(parser, function, visibility, skip, false)
+        }
+        TokenKind::Using => {
+            parser.expect(TokenKind::Using)?;
+            parse_using(parser, &mut function.imports, Visibility::Private)
+        }
+        _ => Err(parser.unexpected("a statement")),
+    }
+}
+
+fn parse_paren_list<T>(
+    parser: &mut Parser,
+    mut parse_item: impl FnMut(&mut Parser) -> Result<T, ParseError>,
+) -> Result<Vec<T>, ParseError> {
+    let open = parser.expect(TokenKind::ParenLeft)?.span.clone();
+
+    // A trailing comma isn't allowed.
+    let mut items = Vec::new();
+    if parser.peek() != TokenKind::ParenRight {
+        loop {
+            items.push(parse_item(parser)?);
+            if parser.peek() != TokenKind::Comma {
+                break;
             }
-            TokenKind::BraceRight => break,
-            t => panic!("{:?}", t),
+            parser.expect(TokenKind::Comma)?;
+        }
+    }
+
+    parser.expect_closing(TokenKind::ParenRight, open)?;
+    Ok(items)
+}
+
+fn parse_ident(parser: &mut Parser) -> Result<UnresolvedIdent, ParseError> {
+    parse_path(parser, &mut None)
+}
+
+// Parts can be separated with `::` instead of `.`, but a path has to stick with one. Whichever
+// the path uses is left in `separator`, which an import's group or glob has to match. A trailing
+// `.*` or `.{`, or `::*` or `::{`, belongs to an import, so it's left for the caller.
+fn parse_path(
+    parser: &mut Parser,
+    separator: &mut Option<TokenKind>,
+) -> Result<UnresolvedIdent, ParseError> {
+    let first = parse_ident_part(parser)?;
+    let mut span = first.1.clone();
+    let mut parts = vec![first];
+
+    while matches!(parser.peek(), TokenKind::Dot | TokenKind::ColonColon)
+        && !matches!(parser.peek_nth(1), TokenKind::Star | TokenKind::BraceLeft)
+    {
+        parse_separator(parser, separator)?;
+        let part = parse_ident_part(parser)?;
+        span.end = part.1.end;
+        parts.push(part);
+    }
+
+    Ok(UnresolvedIdent { parts, span })
+}
+
+fn parse_separator(
+    parser: &mut Parser,
+    separator: &mut Option<TokenKind>,
+) -> Result<(), ParseError> {
+    let kind = parser.peek();
+    let token = parser.expect(kind)?;
+    match *separator {
+        Some(kind) if kind != token.kind => Err(ParseError {
+            message: "cannot mix `.` and `::` in a path".to_owned(),
+            span: token.span,
+        }),
+        _ => {
+            *separator = Some(token.kind);
+            Ok(())
         }
     }
+}
 
-    database.set_unresolved_body(func_id, ast);
+fn parse_ident_part(parser: &mut Parser) -> Result<(String, Range<usize>), ParseError> {
+    // The lexer only produces valid names, but tokens can also come from elsewhere, and a bad
+    // part would otherwise just quietly fail to resolve.
+    let token = parser.expect(TokenKind::Ident)?;
+    if !is_valid_ident(&token.lexeme) {
+        let message = if token.lexeme.is_empty() {
+            "identifier is empty".to_owned()
+        } else {
+            format!("`{}` is not a valid identifier", token.lexeme)
+        };
+        return Err(ParseError {
+            message,
+            span: token.span,
+        });
+    }
 
-    parser.expect(TokenKind::BraceRight);
+    Ok((token.lexeme, token.span))
 }
 
-fn parse_ident(parser: &mut Parser) -> UnresolvedIdent {
-    let mut parts = vec![parser.expect(TokenKind::Ident).lexeme.clone()];
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_ok(source: &str) -> Database {
+        let mut database = Database::new();
+        parse_source(&mut database, source).unwrap();
+        database
+    }
+
+    fn parse_err(source: &str) -> Vec<ParseError> {
+        parse_source(&mut Database::new(), source).unwrap_err()
+    }
+
+    #[test]
+    fn function_params() {
+        let database = parse_ok("function a() {} function b(x) {} function c(x, y, z) {}");
+        let params = |name| database.params_of(database.lookup_path(&[name]).unwrap());
+        assert!(params("a").is_empty());
+        assert_eq!(params("b"), ["x"]);
+        assert_eq!(params("c"), ["x", "y", "z"]);
+
+        let errors = parse_err("function f(x,) {}");
+        assert_eq!(errors[0].message, "expected Ident, found ParenRight");
+        assert_eq!(errors[0].span, 13..14);
+    }
+
+    #[test]
+    fn recovery_skips_broken_blocks() {
+        // The function's body is skipped along with the rest of it, so the module's own closing
+        // brace isn't mistaken for a stray one.
+        let errors = parse_err("module Z { function f(a,){} function g() {} }");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "expected Ident, found ParenRight");
+
+        let mut database = Database::new();
+        let errors = parse_source(
+            &mut database,
+            "function f() {
+                a(,);
+                b(;
+                c();
+            }",
+        )
+        .unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].message, "expected Ident, found Comma");
+        assert_eq!(errors[1].message, "expected Ident, found Semicolon");
+
+        let f = database.lookup_path(&["f"]).unwrap();
+        let body = database.get_unresolved_body(f);
+        assert_eq!(body.len(), 1);
+        let UnresolvedAST::Call { ident, .. } = &body[0];
+        assert_eq!(ident.to_string(), "c");
+    }
+
+    #[test]
+    fn unclosed_block_points_at_its_brace() {
+        let errors = parse_err("module A {\n    function f() {}\n");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].message,
+            "expected BraceRight, found Eof; this delimiter is never closed"
+        );
+        assert_eq!(errors[0].span, 9..10);
+    }
+
+    #[test]
+    fn second_crate_name_points_at_the_name() {
+        let errors = parse_err("crate First {}\ncrate Second {}");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].message,
+            "crate `First` is already named, it can't also be `Second`"
+        );
+        assert_eq!(errors[0].span, 21..27);
+    }
+
+    #[test]
+    fn ident_parts_keep_their_spans() {
+        let database = parse_ok("function f() { A.b(); }");
+        let f = database.lookup_path(&["f"]).unwrap();
+        let UnresolvedAST::Call { ident, .. } = &database.get_unresolved_body(f)[0];
+
+        assert_eq!(ident.parts[1], ("b".to_owned(), 17..18));
+        assert_eq!(ident.span, 15..18);
+        assert_eq!(format!("{:?}", ident), "UnresolvedIdent(A.b)");
+    }
+
+    #[test]
+    fn missing_semicolon() {
+        let mut database = Database::new();
+        let errors = parse_source(&mut database, "function f() { a() b(); }").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "expected Semicolon, found Ident");
+        assert_eq!(errors[0].span, 18..18);
+
+        let f = database.lookup_path(&["f"]).unwrap();
+        let calls: Vec<_> = database
+            .get_unresolved_body(f)
+            .iter()
+            .map(|UnresolvedAST::Call { ident, .. }| ident.to_string())
+            .collect();
+        assert_eq!(calls, ["a", "b"]);
+    }
+
+    #[test]
+    fn malformed_ident_parts() {
+        // The lexer never makes these, but tokens can come from anywhere.
+        let mut tokens = crate::lexer::lex("function f() { A.b(); }").unwrap();
+        let b = tokens.iter_mut().find(|t| t.lexeme == "b").unwrap();
+        b.lexeme = String::new();
+
+        let errors = parse(&mut Database::new(), &tokens).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "identifier is empty");
+        assert_eq!(errors[0].span, 17..18);
+
+        let b = tokens.iter_mut().find(|t| t.span == (17..18)).unwrap();
+        b.lexeme = "9lives".to_owned();
+        let errors = parse(&mut Database::new(), &tokens).unwrap_err();
+        assert_eq!(errors[0].message, "`9lives` is not a valid identifier");
+    }
+
+    #[test]
+    fn stray_closing_brace() {
+        // Parsing carries on after it, so the items on either side are still declared.
+        let mut database = Database::new();
+        let errors = parse_source(&mut database, "function f() {} } function g() {}").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "expected an item, found BraceRight");
+        assert_eq!(errors[0].span, 16..17);
+        assert!(database.lookup_path(&["f"]).is_some());
+        assert!(database.lookup_path(&["g"]).is_some());
+    }
+
+    #[test]
+    fn skip_needs_a_function_or_module() {
+        let errors = parse_err("#[skip] using A;");
+        assert_eq!(
+            errors[0].message,
+            "`#[skip]` can only be used on a function or module"
+        );
+        assert_eq!(errors[0].span, 0..7);
+    }
 
-    while parser.peek() == TokenKind::Dot {
-        parser.expect(TokenKind::Dot);
-        parts.push(parser.expect(TokenKind::Ident).lexeme.clone());
+    #[test]
+    fn lone_underscore_is_not_a_name() {
+        let errors = parse_err("function _() {}");
+        assert_eq!(errors[0].message, "expected Ident, found Underscore");
+        assert_eq!(errors[0].span, 9..10);
+        parse_ok("function _f() { __(); } function __() {}");
     }
 
-    UnresolvedIdent { parts }
+    #[test]
+    fn item_tree_shape() {
+        let tokens = crate::lexer::lex(
+            "using A.*;
+            crate MyCrate {
+                module A { pub function f() {} }
+                alias B = A;
+            }
+            function g(x) { f(); function inner() {} using A.f; }",
+        )
+        .unwrap();
+        let root = parse_to_items(&tokens).unwrap();
+
+        assert_eq!(root.name, "MyCrate");
+        assert!(root.is_named_crate());
+        assert_eq!(root.imports.len(), 1);
+        assert!(root.imports[0].glob);
+
+        let names: Vec<_> = root.children.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["A", "B", "g"]);
+
+        let a = &root.children[0];
+        assert_eq!(a.kind, ItemKind::Module);
+        assert_eq!(a.children[0].name, "f");
+        assert_eq!(a.children[0].visibility, Visibility::Public);
+        assert!(a.children[0].empty);
+
+        let b = &root.children[1];
+        assert_eq!(b.kind, ItemKind::Alias);
+        assert_eq!(b.alias_target.as_ref().unwrap().to_string(), "A");
+
+        let g = &root.children[2];
+        assert_eq!(g.params, ["x"]);
+        assert_eq!(g.body.len(), 1);
+        assert!(!g.empty);
+        assert_eq!(g.children[0].name, "inner");
+        assert_eq!(g.imports[0].path.to_string(), "A.f");
+    }
+
+    #[test]
+    fn mixed_path_separators() {
+        let errors = parse_err("function f() { A::inner.f(); }");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "cannot mix `.` and `::` in a path");
+        assert_eq!(errors[0].span, 23..24);
+    }
+
+    #[test]
+    fn empty_import_group() {
+        let errors = parse_err("using A.{};\nusing B.{ };");
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].message, "empty import group in `using A`");
+        assert_eq!(errors[0].span, 8..10);
+        assert_eq!(errors[1].message, "empty import group in `using B`");
+        assert_eq!(errors[1].span, 20..23);
+    }
+
+    #[test]
+    fn double_colon_globs_and_groups() {
+        let database = parse_ok("using A::*; using A::B::{c, d::e}; module A {}");
+        let root = database.root();
+        let paths: Vec<_> = database
+            .pending_imports(root)
+            .iter()
+            .map(|i| (i.path.to_string(), i.glob))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                ("A".to_owned(), true),
+                ("A.B.c".to_owned(), false),
+                ("A.B.d.e".to_owned(), false)
+            ]
+        );
+
+        // The glob or group has to use the same separator as the rest of the path.
+        for (source, span) in [
+            ("using A::B.*;", 10..11),
+            ("using A.B::{c};", 9..11),
+            ("using A::{b.c};", 11..12),
+        ] {
+            let errors = parse_err(source);
+            assert_eq!(
+                errors[0].message, "cannot mix `.` and `::` in a path",
+                "{}",
+                source
+            );
+            assert_eq!(errors[0].span, span, "{}", source);
+        }
+    }
 }
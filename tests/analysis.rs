@@ -0,0 +1,137 @@
+use simple_ident_res::{database::ItemId, resolve_str, Database};
+
+fn item(database: &Database, path: &str) -> ItemId {
+    let parts: Vec<_> = path.split('.').collect();
+    database.lookup_path(&parts).unwrap()
+}
+
+#[test]
+fn references_to_lists_every_caller() {
+    let database = resolve_str(
+        "function target() {}
+        function first() { target(); }
+        function second() { target(); target(); }
+        function unrelated() {}",
+    )
+    .unwrap();
+
+    assert_eq!(
+        database.references_to(item(&database, "target")),
+        [item(&database, "first"), item(&database, "second")]
+    );
+}
+
+#[test]
+fn orphan_items() {
+    let database = resolve_str(
+        "module A {
+            module inner {
+                pub function used() {}
+                function unused() {}
+            }
+            pub function caller() { inner.used(); }
+        }",
+    )
+    .unwrap();
+
+    // `inner` has a used child, so it isn't an orphan, but nothing calls `caller` either.
+    let orphans: Vec<_> = database
+        .orphan_items()
+        .into_iter()
+        .map(|id| database.full_path(id))
+        .collect();
+    assert_eq!(orphans, ["A.inner.unused", "A.caller"]);
+}
+
+#[test]
+fn reachable_from_entry() {
+    let database = resolve_str(
+        "entry function main() { a(); }
+        function a() { b(); }
+        function b() {}
+        function unused() { a(); }",
+    )
+    .unwrap();
+
+    assert_eq!(database.entry(), Some(item(&database, "main")));
+    let reachable = database.reachable_from_entry();
+    assert_eq!(reachable.len(), 3);
+    for name in ["main", "a", "b"] {
+        assert!(reachable.contains(&item(&database, name)), "{}", name);
+    }
+    assert!(!reachable.contains(&item(&database, "unused")));
+}
+
+#[test]
+fn walk_resolved_counts_calls() {
+    use std::collections::HashMap;
+
+    use simple_ident_res::ast::ResolvedVisitor;
+
+    #[derive(Default)]
+    struct CallCounter(HashMap<ItemId, usize>);
+
+    impl ResolvedVisitor for CallCounter {
+        fn visit_call(&mut self, _caller: ItemId, target: ItemId) {
+            *self.0.entry(target).or_default() += 1;
+        }
+    }
+
+    let database = resolve_str(
+        "module A { pub function b() { pub function c() {} } }
+        function f() { A.b(); A.b().c(); }
+        function g() { f(); }",
+    )
+    .unwrap();
+    let mut counter = CallCounter::default();
+    database.walk_resolved(&mut counter);
+
+    // Every link in a chain is a call of its own.
+    assert_eq!(counter.0[&item(&database, "A.b")], 2);
+    assert_eq!(
+        counter.0[&database
+            .children_of(item(&database, "A.b"))
+            .next()
+            .unwrap()
+            .1],
+        1
+    );
+    assert_eq!(counter.0[&item(&database, "f")], 1);
+    assert_eq!(counter.0.len(), 3);
+}
+
+#[test]
+fn direct_recursion() {
+    let database = resolve_str(
+        "function fact() { fact(); }
+        function ping() { pong(); }
+        function pong() { ping(); }
+        function plain() { fact(); }",
+    )
+    .unwrap();
+
+    // Calling itself through `pong` doesn't count.
+    assert_eq!(database.direct_recursion(), [item(&database, "fact")]);
+}
+
+#[test]
+fn call_cycles() {
+    let database = resolve_str(
+        "function f() { g(); }
+        function g() { f(); }
+        function h() { h(); }
+        function caller() { f(); h(); }",
+    )
+    .unwrap();
+
+    // `caller` reaches both cycles, but isn't part of either.
+    let mut cycles = database.call_cycles();
+    cycles.sort_unstable();
+    assert_eq!(
+        cycles,
+        [
+            vec![item(&database, "f"), item(&database, "g")],
+            vec![item(&database, "h")]
+        ]
+    );
+}
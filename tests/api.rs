@@ -0,0 +1,439 @@
+use simple_ident_res::{
+    ast::{ResolvedAST, ResolvedIdent},
+    database::ItemKind,
+    lex, parse_source, parse_to_items, resolve_str, Database, DatabaseBuilder,
+};
+
+#[test]
+fn resolve_str_builds_a_database() {
+    let database = resolve_str(
+        "module A { pub function f() {} }
+        function g() { A.f(); }",
+    )
+    .unwrap();
+
+    assert_eq!(database.diagnostics(), []);
+    let f = database.lookup_path(&["A", "f"]).unwrap();
+    assert_eq!(database.kind_of(f), ItemKind::Function);
+    assert_eq!(database.full_path(f), "A.f");
+
+    let g = database.lookup_path(&["g"]).unwrap();
+    assert_eq!(database.references_to(f), [g]);
+}
+
+#[test]
+fn resolve_str_errors_render_with_a_caret() {
+    let source = "module A1 { @ }";
+    let Err(diagnostics) = resolve_str(source) else {
+        panic!("expected a lex error");
+    };
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        Database::render_diagnostic(source, &diagnostics[0]),
+        "error: unrecognized input `@`\n --> 1:13\n  |\n1 | module A1 { @ }\n  |             ^\n"
+    );
+}
+
+#[test]
+fn lookup_path_walks_from_the_root() {
+    let database =
+        resolve_str("module A { pub module inner { pub module deep { pub function f() {} } } }")
+            .unwrap();
+
+    let f = database.lookup_path(&["A", "inner", "deep", "f"]).unwrap();
+    assert_eq!(database.name_of(f), "f");
+    assert_eq!(database.lookup_path(&["A", "missing", "deep", "f"]), None);
+    // Functions can't be walked through.
+    assert_eq!(
+        database.lookup_path(&["A", "inner", "deep", "f", "x"]),
+        None
+    );
+}
+
+#[test]
+fn children_come_back_sorted() {
+    let database =
+        resolve_str("module M { function zeta() {} module beta {} function alpha() {} }").unwrap();
+
+    let m = database.lookup_path(&["M"]).unwrap();
+    let names: Vec<_> = database.children_of(m).map(|(name, _)| name).collect();
+    assert_eq!(names, ["alpha", "beta", "zeta"]);
+
+    let zeta = database.lookup_path(&["M", "zeta"]).unwrap();
+    assert_eq!(database.children_of(zeta).count(), 0);
+}
+
+#[test]
+fn root_is_not_its_own_child() {
+    for source in ["module M {}", "crate Named { module M {} }"] {
+        let database = resolve_str(source).unwrap();
+        let names: Vec<_> = database
+            .children_of(database.root())
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, ["M"], "{}", source);
+    }
+}
+
+#[test]
+fn paths_from_item_metadata() {
+    let database = resolve_str(
+        "module A { pub module inner { pub function f() {} } }
+        function g() { A.inner.f(); }",
+    )
+    .unwrap();
+
+    let g = database.lookup_path(&["g"]).unwrap();
+    let ResolvedAST::Call {
+        ident: ResolvedIdent::Item(target),
+        ..
+    } = &database.view().resolved_body(g)[0]
+    else {
+        panic!("the call didn't resolve");
+    };
+    assert_eq!(database.kind_of(*target), ItemKind::Function);
+
+    let mut parts = Vec::new();
+    let mut id = *target;
+    while id != database.root() {
+        parts.push(database.name_of(id));
+        id = database.parent_of(id);
+    }
+    parts.reverse();
+    assert_eq!(parts.join("."), "A.inner.f");
+}
+
+#[test]
+fn full_paths() {
+    let database =
+        resolve_str("module Top { pub module a { pub module b { pub function f() {} } } }")
+            .unwrap();
+
+    let f = database.lookup_path(&["Top", "a", "b", "f"]).unwrap();
+    assert_eq!(database.full_path(f), "Top.a.b.f");
+    let top = database.lookup_path(&["Top"]).unwrap();
+    assert_eq!(database.full_path(top), "Top");
+}
+
+#[test]
+fn builder_matches_parsed_source() {
+    let mut built = DatabaseBuilder::new()
+        .module("A", |a| {
+            a.pub_function("f", |_| {});
+        })
+        .module("B", |b| {
+            b.import("crate.A.f");
+            b.function("g", |g| {
+                g.call("f", &[]).call("crate.A.f", &[]);
+            });
+        })
+        .build();
+    let parsed = resolve_str(
+        "module A { pub function f() {} }
+        module B { using crate.A.f; function g() { f(); crate.A.f(); } }",
+    )
+    .unwrap();
+
+    assert_eq!(built.resolve_idents(), []);
+    assert_eq!(built.symbol_table(), parsed.symbol_table());
+    assert_eq!(built.resolved_ast_pretty(), parsed.resolved_ast_pretty());
+}
+
+#[test]
+fn items_of_kind() {
+    let database = resolve_str(
+        "function a() {}
+        module M { function b() {} module N {} }",
+    )
+    .unwrap();
+
+    let functions: Vec<_> = database.items_of_kind(ItemKind::Function).collect();
+    assert_eq!(
+        functions,
+        [
+            database.lookup_path(&["a"]).unwrap(),
+            database.lookup_path(&["M", "b"]).unwrap()
+        ]
+    );
+
+    let modules: Vec<_> = database.items_of_kind(ItemKind::Module).collect();
+    assert!(!modules.contains(&database.root()));
+    assert_eq!(modules.len(), 2);
+}
+
+#[test]
+fn pending_imports_before_resolution() {
+    let mut database = Database::new();
+    simple_ident_res::parse_source(
+        &mut database,
+        "module A { pub function f() {} }
+        module M { using crate.A.f; using crate.A as Other; }",
+    )
+    .unwrap();
+
+    let m = database.lookup_path(&["M"]).unwrap();
+    let imports: Vec<_> = database
+        .pending_imports(m)
+        .iter()
+        .map(|import| (import.path.to_string(), import.bound_name().to_owned()))
+        .collect();
+    assert_eq!(
+        imports,
+        [
+            ("crate.A.f".to_owned(), "f".to_owned()),
+            ("crate.A".to_owned(), "Other".to_owned())
+        ]
+    );
+}
+
+#[test]
+fn rename_item() {
+    let mut database = resolve_str(
+        "function old() {}
+        function taken() {}
+        function caller() { old(); }",
+    )
+    .unwrap();
+    let id = database.lookup_path(&["old"]).unwrap();
+
+    assert!(database.rename_item(id, "taken".to_owned()).is_err());
+    assert!(database.rename_item(id, "not valid".to_owned()).is_err());
+    assert!(database.rename_item(id, String::new()).is_err());
+    assert_eq!(database.lookup_path(&["old"]), Some(id));
+
+    database.rename_item(id, "new".to_owned()).unwrap();
+    assert_eq!(database.lookup_path(&["old"]), None);
+    assert_eq!(database.lookup_path(&["new"]), Some(id));
+    assert_eq!(database.name_of(id), "new");
+
+    // The call still names the old name.
+    assert_eq!(database.resolve_idents().len(), 1);
+}
+
+#[test]
+fn remove_item_returns_its_callers() {
+    let mut database = resolve_str(
+        "module A { pub function f() {} pub function h() {} }
+        function g() { A.f(); A.h(); }
+        function k() { A.f(); }
+        function m() { A.h(); }",
+    )
+    .unwrap();
+    let f = database.lookup_path(&["A", "f"]).unwrap();
+    let g = database.lookup_path(&["g"]).unwrap();
+    let k = database.lookup_path(&["k"]).unwrap();
+
+    assert_eq!(database.remove_item(f), [g, k]);
+    assert_eq!(database.lookup_path(&["A", "f"]), None);
+    assert_eq!(database.references_to(f), []);
+
+    // Calls to it are unresolved again, so nothing reaches its header.
+    let ResolvedAST::Call { ident, .. } = &database.view().resolved_body(k)[0];
+    assert!(matches!(ident, ResolvedIdent::Unresolved(_)));
+    assert!(!database.to_dot().contains("f (Function)"));
+    assert!(database.statistics().contains("unresolved references: 2"));
+}
+
+#[test]
+fn resolved_view() {
+    let mut database = resolve_str(
+        "module A { pub function f() {} }
+        using A.f;
+        function g() { f(); }
+        function gone() {}",
+    )
+    .unwrap();
+    let gone = database.lookup_path(&["gone"]).unwrap();
+    database.remove_item(gone);
+
+    let view = database.view();
+    let names: Vec<_> = view.headers().map(|h| h.name()).collect();
+    assert_eq!(names, ["<ROOT>", "A", "f", "g"]);
+
+    let f = database.lookup_path(&["A", "f"]).unwrap();
+    let g = database.lookup_path(&["g"]).unwrap();
+    let ResolvedAST::Call { ident, .. } = &view.resolved_body(g)[0];
+    assert!(matches!(ident, ResolvedIdent::Item(id) if *id == f));
+    assert_eq!(view.resolved_bodies().count(), 2);
+
+    // The import shows up as a child of the root.
+    let mut children: Vec<_> = view.children(database.root()).collect();
+    children.sort_unstable();
+    assert_eq!(
+        children,
+        [
+            ("A", database.lookup_path(&["A"]).unwrap()),
+            ("f", f),
+            ("g", g)
+        ]
+    );
+}
+
+#[test]
+fn parse_to_items_then_load() {
+    let tokens =
+        lex("using A.*; crate MyCrate { module A { pub function f(){} } } function g(){ f(); }")
+            .unwrap();
+    let tree = parse_to_items(&tokens).unwrap();
+
+    let mut database = Database::new();
+    let root = database.root();
+    database.load_items(root, tree);
+
+    assert_eq!(database.resolve_idents(), []);
+    assert_eq!(database.name_of(root), "MyCrate");
+    let f = database.lookup_path(&["A", "f"]).unwrap();
+    let g = database.lookup_path(&["g"]).unwrap();
+    assert_eq!(database.references_to(f), [g]);
+}
+
+#[test]
+fn merge_grafts_items_under_a_module() {
+    let mut database = Database::new();
+    parse_source(
+        &mut database,
+        "module Lib {}
+        using Lib.g as h if fast;
+        function main() { Lib.f(); Lib.g(); h(); }",
+    )
+    .unwrap();
+    let mut other = Database::new();
+    parse_source(
+        &mut other,
+        "crate Other {
+            module A { pub function f() {} }
+            pub using A.f;
+            pub function g() { crate.A.f(); }
+        }",
+    )
+    .unwrap();
+    other.enable_feature("fast");
+
+    let lib = database.lookup_path(&["Lib"]).unwrap();
+    database.merge(other, lib);
+
+    // The feature was only enabled in `other`, so `h` still isn't imported.
+    let diagnostics = database.resolve_idents();
+    assert_eq!(diagnostics.len(), 1);
+    assert!(diagnostics[0].message.starts_with("symbol `h` not found"));
+
+    // Its crate name is dropped, and inside the merged items `crate` now means `Lib`.
+    assert_eq!(database.name_of(database.root()), "<ROOT>");
+    let f = database.lookup_path(&["Lib", "A", "f"]).unwrap();
+    let g = database.lookup_path(&["Lib", "g"]).unwrap();
+    assert_eq!(database.full_path(g), "Lib.g");
+    let main = database.lookup_path(&["main"]).unwrap();
+    assert_eq!(database.references_to(f), [main, g]);
+
+    // Clashing names are reported like any other duplicate.
+    let mut clash = Database::new();
+    parse_source(&mut clash, "function main() {}").unwrap();
+    let root = database.root();
+    database.merge(clash, root);
+    assert!(database
+        .resolve_idents()
+        .iter()
+        .any(|d| d.message.starts_with("`main` is declared more than once")));
+}
+
+#[test]
+fn merged_items_keep_their_own_crate_paths() {
+    let mut database = Database::new();
+    parse_source(
+        &mut database,
+        "module Lib {} module X { pub function f() {} }",
+    )
+    .unwrap();
+    let mut other = Database::new();
+    parse_source(
+        &mut other,
+        "module X { pub function f() {} }
+        module Y { module Z { function g() { X.f(); crate.X.f(); } } }",
+    )
+    .unwrap();
+
+    let lib = database.lookup_path(&["Lib"]).unwrap();
+    database.merge(other, lib);
+    assert_eq!(database.resolve_idents(), []);
+
+    // Both calls find the merged `X`, not the one already at the top level, which only items
+    // outside the merge still see.
+    parse_source(&mut database, "function h() { X.f(); crate.X.f(); }").unwrap();
+    assert_eq!(database.resolve_idents(), []);
+    let merged_f = database.lookup_path(&["Lib", "X", "f"]).unwrap();
+    let top_f = database.lookup_path(&["X", "f"]).unwrap();
+    let g = database.lookup_path(&["Lib", "Y", "Z", "g"]).unwrap();
+    let h = database.lookup_path(&["h"]).unwrap();
+    assert_eq!(database.references_to(merged_f), [g]);
+    assert_eq!(database.references_to(top_f), [h]);
+}
+
+#[test]
+fn remove_item_cleans_up_duplicates() {
+    let source =
+        "function foo() {} function foo() {} function foo() {} function caller() { foo(); }";
+
+    // Removing a later one leaves the other duplicate reported, and nothing about the removed one.
+    let mut database = Database::new();
+    parse_source(&mut database, source).unwrap();
+    let first = database.lookup_path(&["foo"]).unwrap();
+    let diagnostics = database.resolve_idents();
+    let (second, third) = {
+        let mut ids = database
+            .view()
+            .headers()
+            .map(|h| h.id())
+            .filter(|&id| database.name_of(id) == "foo" && id != first);
+        (ids.next().unwrap(), ids.next().unwrap())
+    };
+    assert_eq!(diagnostics.len(), 2);
+
+    database.remove_item(second);
+    let diagnostics = database.resolve_idents();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].message,
+        format!(
+            "`foo` is declared more than once: {:?} and {:?}",
+            first, third
+        )
+    );
+    assert_eq!(database.lookup_path(&["foo"]), Some(first));
+
+    // Removing the one that was bound lets the next take its place.
+    let mut database = Database::new();
+    parse_source(&mut database, source).unwrap();
+    database.remove_item(first);
+    let diagnostics = database.resolve_idents();
+    assert_eq!(database.lookup_path(&["foo"]), Some(second));
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].message,
+        format!(
+            "`foo` is declared more than once: {:?} and {:?}",
+            second, third
+        )
+    );
+    database.remove_item(third);
+    assert_eq!(database.resolve_idents(), []);
+    let caller = database.lookup_path(&["caller"]).unwrap();
+    assert_eq!(database.references_to(second), [caller]);
+}
+
+#[test]
+fn remove_item_hands_on_the_entry_point() {
+    let mut database = Database::new();
+    parse_source(&mut database, "entry function a() {} entry function b() {}").unwrap();
+    let a = database.lookup_path(&["a"]).unwrap();
+    let b = database.lookup_path(&["b"]).unwrap();
+    assert_eq!(database.entry(), Some(a));
+    assert_eq!(
+        database.resolve_idents()[0].message,
+        "more than one entry point: `a` and `b`"
+    );
+
+    database.remove_item(a);
+    assert_eq!(database.entry(), Some(b));
+    assert_eq!(database.resolve_idents(), []);
+}
@@ -0,0 +1,40 @@
+use simple_ident_res::{database::ItemOrigin, Database};
+
+#[test]
+fn files_reference_each_other() {
+    let mut database = Database::new();
+    database
+        .add_file(
+            &["app", "client"],
+            "pub function request() { crate.app.server.respond(); }",
+        )
+        .unwrap();
+    database
+        .add_file(
+            &["app", "server"],
+            "pub function respond() {} function log() { crate.app.client.request(); }",
+        )
+        .unwrap();
+
+    assert_eq!(database.resolve_idents(), []);
+    let request = database.lookup_path(&["app", "client", "request"]).unwrap();
+    let respond = database.lookup_path(&["app", "server", "respond"]).unwrap();
+    let log = database.lookup_path(&["app", "server", "log"]).unwrap();
+    assert_eq!(database.references_to(respond), [request]);
+    assert_eq!(database.references_to(request), [log]);
+}
+
+#[test]
+fn item_origins() {
+    let mut database = Database::new();
+    database
+        .add_file(&["lib", "util"], "pub function helper() {}")
+        .unwrap();
+    assert_eq!(database.resolve_idents(), []);
+
+    assert_eq!(database.origin_of(database.root()), ItemOrigin::Root);
+    let util = database.lookup_path(&["lib", "util"]).unwrap();
+    assert_eq!(database.origin_of(util), ItemOrigin::File);
+    let helper = database.lookup_path(&["lib", "util", "helper"]).unwrap();
+    assert_eq!(database.origin_of(helper), ItemOrigin::Source);
+}
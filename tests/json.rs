@@ -0,0 +1,79 @@
+#![cfg(feature = "serde")]
+
+use serde_json::Value;
+use simple_ident_res::{parse_source, resolve_str, Database};
+
+#[test]
+fn to_json_has_names_and_call_targets() {
+    let database = resolve_str(
+        "module A { pub function f() {} }
+        function g() { A.f(); }",
+    )
+    .unwrap();
+    let json: Value = serde_json::from_str(&database.to_json()).unwrap();
+
+    let headers = json["headers"].as_array().unwrap();
+    let names: Vec<_> = headers
+        .iter()
+        .map(|h| h["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["<ROOT>", "A", "f", "g"]);
+
+    // Nothing has been removed, so ids are just their index into the headers.
+    let g_body = &json["resolved_bodies"]["3"];
+    assert_eq!(g_body[0]["Call"]["ident"]["Item"], 2);
+    assert_eq!(headers[2]["kind"], "Function");
+    assert_eq!(headers[2]["parent"], 1);
+}
+
+#[test]
+fn to_json_leaves_out_removed_items() {
+    let mut database = resolve_str(
+        "module A { pub function f() {} }
+        function g() { A.f(); }",
+    )
+    .unwrap();
+    let f = database.lookup_path(&["A", "f"]).unwrap();
+    database.remove_item(f);
+    let json: Value = serde_json::from_str(&database.to_json()).unwrap();
+
+    let names: Vec<_> = json["headers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|h| h["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["<ROOT>", "A", "g"]);
+    assert!(json["resolved_bodies"]["3"][0]["Call"]["ident"]["Unresolved"].is_object());
+}
+
+#[test]
+fn diagnostics_json_has_line_and_column() {
+    let source = "function f() {}\nfunction g() {\n    f();\n    missing();\n}";
+    let mut database = Database::new();
+    parse_source(&mut database, source).unwrap();
+    database.resolve_idents();
+    let json: Value = serde_json::from_str(&database.diagnostics_json(source)).unwrap();
+
+    let diagnostics = json.as_array().unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["severity"], "error");
+    assert_eq!(diagnostics[0]["item"], "g");
+    assert_eq!(diagnostics[0]["start"]["line"], 4);
+    assert_eq!(diagnostics[0]["start"]["column"], 5);
+    assert_eq!(diagnostics[0]["start"]["offset"], 44);
+    assert_eq!(diagnostics[0]["end"]["line"], 4);
+    assert_eq!(diagnostics[0]["end"]["column"], 12);
+}
+
+#[test]
+fn diagnostics_json_names_the_root() {
+    let source = "using missing;";
+    let mut database = Database::new();
+    parse_source(&mut database, source).unwrap();
+    database.resolve_idents();
+    let json: Value = serde_json::from_str(&database.diagnostics_json(source)).unwrap();
+
+    assert_eq!(json[0]["message"], "symbol `missing` not found");
+    assert_eq!(json[0]["item"], "<ROOT>");
+}
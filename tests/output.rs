@@ -0,0 +1,202 @@
+use simple_ident_res::{parse_source, resolve_str, ChildOrder, Database};
+
+#[test]
+fn dot_graph_has_dashed_call_edges() {
+    let database = resolve_str(
+        "module A { pub function f() {} }
+        module B { function g() { crate.A.f(); } }",
+    )
+    .unwrap();
+
+    assert_eq!(
+        database.to_dot(),
+        "digraph {
+    1 [label=\"A (Module)\"];
+    2 [label=\"f (Function)\"];
+    3 [label=\"B (Module)\"];
+    4 [label=\"g (Function)\"];
+    1 -> 2;
+    3 -> 4;
+    4 -> 2 [style=dashed];
+}
+"
+    );
+}
+
+#[test]
+fn pretty_resolved_ast() {
+    let mut database = Database::new();
+    parse_source(
+        &mut database,
+        "module A { pub function f() { crate.B.g(); missing.thing(); } }
+        module B { pub function g() {} }",
+    )
+    .unwrap();
+    database.resolve_idents();
+
+    assert_eq!(
+        database.resolved_ast_pretty(),
+        "A.f
+    A.f -> B.g
+    A.f -> <unresolved: missing.thing>
+B.g
+"
+    );
+}
+
+#[test]
+fn child_lookup_and_print_order() {
+    let database = resolve_str(
+        "module M { pub function c() {} pub function a() {} pub module b {} }
+        function caller() { M.a(); M.c(); }",
+    )
+    .unwrap();
+
+    for name in ["a", "b", "c"] {
+        let id = database.lookup_path(&["M", name]).unwrap();
+        assert_eq!(database.name_of(id), name);
+    }
+
+    assert_eq!(
+        database.tree(ChildOrder::Alphabetical),
+        "M [Module]
+    a [Function]
+    b [Module]
+    c [Function]
+caller [Function]
+"
+    );
+    assert_eq!(
+        database.tree(ChildOrder::Source),
+        "M [Module]
+    c [Function]
+    a [Function]
+    b [Module]
+caller [Function]
+"
+    );
+}
+
+fn render_all(source: &str) -> String {
+    let mut database = Database::new();
+    parse_source(&mut database, source).unwrap();
+    database.set_warn_empty(true);
+    database
+        .resolve_idents()
+        .iter()
+        .map(|d| Database::render_diagnostic(source, d))
+        .collect()
+}
+
+#[test]
+fn rendered_diagnostics_underline_the_problem() {
+    assert_eq!(
+        render_all("function f() {\n    A.missing();\n}\nmodule A {}"),
+        "error: resolved `A` but `A` has no child `missing`
+ --> 2:7
+  |
+2 |     A.missing();
+  |       ^^^^^^^
+"
+    );
+}
+
+#[test]
+fn declaration_diagnostics_point_at_the_name() {
+    let source = "module M {}
+entry function main() { other(); }
+entry function other() { main(); }
+module M {}
+function nothing() {}";
+
+    assert_eq!(
+        render_all(source),
+        "error: `M` is declared more than once: ItemId(1) and ItemId(4)
+ --> 4:8
+  |
+4 | module M {}
+  |        ^
+error: more than one entry point: `main` and `other`
+ --> 3:16
+  |
+3 | entry function other() { main(); }
+  |                ^^^^^
+warning: function `nothing` is empty
+ --> 5:10
+  |
+5 | function nothing() {}
+  |          ^^^^^^^
+"
+    );
+}
+
+#[test]
+fn symbol_table() {
+    let mut database = Database::new();
+    parse_source(
+        &mut database,
+        "crate Demo {
+            module A { pub module inner { pub function f() {} } }
+            using A.inner.f;
+            using A.gone;
+            using A.missing if never;
+            function g() { f(); }
+        }",
+    )
+    .unwrap();
+    database.resolve_idents();
+
+    assert_eq!(
+        database.symbol_table(),
+        "ItemId(0): Demo [Module]
+ItemId(1): A [Module]
+ItemId(2): A.inner [Module]
+ItemId(3): A.inner.f [Function]
+ItemId(4): g [Function]
+Imports in Demo:
+    f -> A.inner.f
+    gone -> <unresolved: A.gone>
+    missing -> <disabled: needs `never`>
+"
+    );
+}
+
+#[test]
+fn source_order_printing() {
+    let database = resolve_str("function z() {} function a() {}").unwrap();
+
+    assert_eq!(
+        database.tree(ChildOrder::Source),
+        "z [Function]\na [Function]\n"
+    );
+    assert_eq!(
+        database.tree(ChildOrder::Alphabetical),
+        "a [Function]\nz [Function]\n"
+    );
+}
+
+#[test]
+fn statistics() {
+    let mut database = Database::new();
+    parse_source(
+        &mut database,
+        "module A { pub function f() {} const V; }
+        using A.f;
+        alias M = A;
+        function g(x) { f(); A.f(V); missing(); M.f().h(); }",
+    )
+    .unwrap();
+    database.resolve_idents();
+
+    // `V` isn't visible from `g`, and `h` doesn't exist, so both count as unresolved along with
+    // `missing`. Calls through the alias and the import still count as resolved.
+    assert_eq!(
+        database.statistics(),
+        "modules: 1
+functions: 2
+imports: 1
+resolved calls: 3
+unresolved references: 3
+"
+    );
+}
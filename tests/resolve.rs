@@ -0,0 +1,1020 @@
+use simple_ident_res::{
+    ast::{ResolvedAST, ResolvedIdent, UnresolvedAST, UnresolvedIdent},
+    database::{ItemId, ItemKind},
+    parse_source, Database, Diagnostic, Severity,
+};
+
+fn resolve(source: &str) -> (Database, Vec<Diagnostic>) {
+    let mut database = Database::new();
+    parse_source(&mut database, source).unwrap();
+    let diagnostics = database.resolve_idents();
+    (database, diagnostics)
+}
+
+// Unlike `lookup_path`, this goes through functions too.
+fn item(database: &Database, path: &str) -> ItemId {
+    path.split('.').fold(database.root(), |parent, name| {
+        let mut children = database.children_of(parent);
+        children.find(|&(child, _)| child == name).unwrap().1
+    })
+}
+
+// What each call in the function's body resolved to, if anything.
+fn call_targets(database: &Database, path: &str) -> Vec<Option<ItemId>> {
+    let body = database.view().resolved_body(item(database, path));
+    body.iter()
+        .map(|ResolvedAST::Call { ident, .. }| match ident {
+            ResolvedIdent::Item(id) => Some(*id),
+            ResolvedIdent::Unresolved(_) => None,
+        })
+        .collect()
+}
+
+#[test]
+fn every_missing_symbol_is_reported() {
+    let (database, diagnostics) =
+        resolve("function f() { missing(); } function g() { also_missing(); }");
+
+    assert_eq!(diagnostics.len(), 2);
+    assert!(diagnostics.iter().all(|d| d.severity == Severity::Error));
+    assert_eq!(diagnostics[0].item, item(&database, "f"));
+    assert_eq!(diagnostics[1].item, item(&database, "g"));
+    assert_eq!(call_targets(&database, "f"), [None]);
+    assert_eq!(call_targets(&database, "g"), [None]);
+}
+
+#[test]
+fn duplicate_declarations_name_both_items() {
+    let (database, diagnostics) = resolve("function foo(){} function foo(){}");
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].message,
+        "`foo` is declared more than once: ItemId(1) and ItemId(2)"
+    );
+    assert_eq!(diagnostics[0].item, database.root());
+    // The first declaration keeps the name.
+    assert_eq!(format!("{:?}", item(&database, "foo")), "ItemId(1)");
+}
+
+#[test]
+fn super_and_crate_paths() {
+    let (database, diagnostics) = resolve(
+        "module TopModule {
+            pub function f() {}
+            function sibling_func() {}
+            module inner {
+                module deeper {
+                    function g() { super.h(); crate.TopModule.f(); }
+                }
+                function h() { super.sibling_func(); }
+            }
+        }",
+    );
+
+    assert_eq!(diagnostics, []);
+    let sibling = item(&database, "TopModule.sibling_func");
+    let h = item(&database, "TopModule.inner.h");
+    let f = item(&database, "TopModule.f");
+    assert_eq!(
+        call_targets(&database, "TopModule.inner.deeper.g"),
+        [Some(h), Some(f)]
+    );
+    assert_eq!(
+        call_targets(&database, "TopModule.inner.h"),
+        [Some(sibling)]
+    );
+}
+
+#[test]
+fn aliased_import() {
+    let (database, diagnostics) = resolve(
+        "module A { pub module inner { pub function f() {} } }
+        using A.inner as shortcut;
+        function g() { shortcut.f(); A.inner.f(); }",
+    );
+
+    assert_eq!(diagnostics, []);
+    let f = item(&database, "A.inner.f");
+    assert_eq!(call_targets(&database, "g"), [Some(f), Some(f)]);
+}
+
+#[test]
+fn glob_import() {
+    let (database, diagnostics) = resolve(
+        "module A { pub function f() {} pub function g() {} }
+        using A.*;
+        function h() { f(); g(); }",
+    );
+
+    assert_eq!(diagnostics, []);
+    let f = item(&database, "A.f");
+    let g = item(&database, "A.g");
+    assert_eq!(call_targets(&database, "h"), [Some(f), Some(g)]);
+}
+
+#[test]
+fn imports_in_any_order() {
+    // The second import is the one the first depends on.
+    let (database, diagnostics) = resolve(
+        "module A { pub module x { pub function y() {} } }
+        module B {
+            using x.y;
+            using crate.A.x;
+            function f() { y(); x.y(); }
+        }",
+    );
+
+    assert_eq!(diagnostics, []);
+    let y = item(&database, "A.x.y");
+    assert_eq!(call_targets(&database, "B.f"), [Some(y), Some(y)]);
+}
+
+#[test]
+fn import_cycle_reported_once() {
+    let (_, diagnostics) = resolve(
+        "module A { pub using crate.B.x; }
+        module B { pub using crate.A.x; }",
+    );
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].message,
+        "import cycle: `crate.B.x` in ItemId(1) -> `crate.A.x` in ItemId(2)"
+    );
+}
+
+#[test]
+fn path_through_a_function() {
+    let (database, diagnostics) = resolve(
+        "module A { pub function some_function() {} }
+        function g() { A.some_function.x(); A.some_function(); }",
+    );
+
+    // The bad path doesn't stop the call after it resolving.
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].message,
+        "cannot resolve `x` in `A.some_function.x`: `some_function` is a function, not a module"
+    );
+    let some_function = item(&database, "A.some_function");
+    assert_eq!(call_targets(&database, "g"), [None, Some(some_function)]);
+}
+
+#[test]
+fn call_arguments_resolve() {
+    let (database, diagnostics) = resolve(
+        "module A { pub function g() {} }
+        function h() {}
+        function f(x, y) {}
+        function caller() { f(A.g, h); }",
+    );
+
+    assert_eq!(diagnostics, []);
+    let body = database.view().resolved_body(item(&database, "caller"));
+    let ResolvedAST::Call { args, .. } = &body[0];
+    let args: Vec<_> = args
+        .iter()
+        .map(|arg| match arg {
+            ResolvedIdent::Item(id) => Some(*id),
+            ResolvedIdent::Unresolved(_) => None,
+        })
+        .collect();
+    assert_eq!(
+        args,
+        [Some(item(&database, "A.g")), Some(item(&database, "h"))]
+    );
+}
+
+#[test]
+fn nested_functions_see_outer_scopes() {
+    let (database, diagnostics) = resolve(
+        "module M {
+            function helper() {}
+            function outer() {
+                function first() { helper(); second(); }
+                function second() {}
+            }
+        }",
+    );
+
+    assert_eq!(diagnostics, []);
+    assert_eq!(
+        call_targets(&database, "M.outer.first"),
+        [
+            Some(item(&database, "M.helper")),
+            Some(item(&database, "M.outer.second"))
+        ]
+    );
+}
+
+#[test]
+fn unused_import_reported() {
+    let (database, diagnostics) = resolve(
+        "module A { pub function used() {} pub function unused() {} }
+        using A.used;
+        using A.unused;
+        using A.missing;
+        using Nowhere.*;
+        function f() { used(); }",
+    );
+
+    let warnings: Vec<_> = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Warning)
+        .collect();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].message, "unused import `A.unused`");
+    assert_eq!(warnings[0].item, database.root());
+    // The imports that failed are errors, not unused.
+    assert_eq!(diagnostics.len(), 3);
+}
+
+#[test]
+fn private_items_stay_in_their_module() {
+    let (database, diagnostics) = resolve(
+        "module A {
+            function secret() {}
+            function inside() { secret(); }
+        }
+        module B { function outside() { crate.A.secret(); } }",
+    );
+
+    assert_eq!(
+        call_targets(&database, "A.inside"),
+        [Some(item(&database, "A.secret"))]
+    );
+    assert_eq!(call_targets(&database, "B.outside"), [None]);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].item, item(&database, "B.outside"));
+    assert!(diagnostics[0].message.contains("private"));
+}
+
+#[test]
+fn diagnostics_sorted_by_item() {
+    let (database, diagnostics) = resolve(
+        "module M { function inner() { zeta(); alpha(); } }
+        function outer() { missing(); }",
+    );
+
+    let items: Vec<_> = diagnostics.iter().map(|d| d.item).collect();
+    let inner = item(&database, "M.inner");
+    let outer = item(&database, "outer");
+    assert_eq!(items, [inner, inner, outer]);
+    // Within an item they're ordered by message.
+    assert!(diagnostics[0].message.contains("`alpha`"));
+    assert!(diagnostics[1].message.contains("`zeta`"));
+}
+
+#[test]
+fn same_missing_symbol_from_two_functions() {
+    let (database, diagnostics) = resolve(
+        "function b() { missing(); }
+        function a() { missing(); }",
+    );
+
+    let items: Vec<_> = diagnostics.iter().map(|d| d.item).collect();
+    assert_eq!(items, [item(&database, "b"), item(&database, "a")]);
+    assert_eq!(diagnostics[0].message, diagnostics[1].message);
+}
+
+#[test]
+fn const_items() {
+    let (database, diagnostics) = resolve(
+        "module A { pub const LIMIT; }
+        function f() { A.LIMIT(); A.LIMIT.x(); }",
+    );
+
+    let limit = item(&database, "A.LIMIT");
+    assert_eq!(database.kind_of(limit), ItemKind::Value);
+    assert_eq!(call_targets(&database, "f"), [Some(limit), None]);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].message,
+        "cannot resolve `x` in `A.LIMIT.x`: `LIMIT` is a value, not a module"
+    );
+}
+
+#[test]
+fn calling_a_module() {
+    let (database, diagnostics) = resolve(
+        "module A { pub module B {} }
+        function f() { A.B(); }",
+    );
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].message,
+        "cannot call `A.B`: it is a module, not a function"
+    );
+    assert_eq!(diagnostics[0].item, item(&database, "f"));
+}
+
+#[test]
+fn resolve_item_only_changes_that_body() {
+    let (mut database, _) = resolve(
+        "function a() {}
+        function b() {}
+        function f() { a(); }
+        function g() { a(); }",
+    );
+    let before = database.resolved_ast_pretty();
+
+    let f = item(&database, "f");
+    let b = UnresolvedIdent {
+        parts: vec![("b".to_owned(), 0..0)],
+        span: 0..0,
+    };
+    database.set_unresolved_body(
+        f,
+        vec![UnresolvedAST::Call {
+            ident: b,
+            args: Vec::new(),
+            chain: Vec::new(),
+        }],
+    );
+    assert_eq!(database.resolve_item(f), []);
+
+    assert_eq!(call_targets(&database, "f"), [Some(item(&database, "b"))]);
+    assert_eq!(call_targets(&database, "g"), [Some(item(&database, "a"))]);
+    assert_eq!(
+        database.resolved_ast_pretty(),
+        before.replace("f -> a", "f -> b")
+    );
+}
+
+#[test]
+fn pub_using_reexports() {
+    let (database, diagnostics) = resolve(
+        "module B { pub function f() {} pub function g() {} }
+        module A { pub using crate.B.f; using crate.B.g; }
+        function caller() { A.f(); A.g(); }",
+    );
+
+    let f = item(&database, "B.f");
+    assert_eq!(call_targets(&database, "caller"), [Some(f), None]);
+    // The private import is also unused, but that's all.
+    let errors: Vec<_> = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .collect();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].item, item(&database, "caller"));
+}
+
+#[test]
+fn glob_ambiguity() {
+    let (database, diagnostics) = resolve(
+        "module X { pub function f() {} pub function h() {} }
+        module Y { pub function f() {} pub function h() {} }
+        using X.*;
+        using Y.*;
+        using X.h;
+        function g() { f(); h(); }",
+    );
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].message,
+        "`f` is ambiguous, in `f`: it could be any of `X.f`, `Y.f`"
+    );
+    // The explicit import beats both globs.
+    assert_eq!(
+        call_targets(&database, "g"),
+        [None, Some(item(&database, "X.h"))]
+    );
+}
+
+#[test]
+fn top_level_functions() {
+    let (database, diagnostics) = resolve(
+        "function helper() {}
+        module A { module inner { function f() { helper(); } } }",
+    );
+
+    assert_eq!(diagnostics, []);
+    assert_eq!(
+        database.parent_of(item(&database, "helper")),
+        database.root()
+    );
+    assert_eq!(
+        call_targets(&database, "A.inner.f"),
+        [Some(item(&database, "helper"))]
+    );
+}
+
+#[test]
+fn alias_items() {
+    let (database, diagnostics) = resolve(
+        "module A { pub module inner { pub function f() {} } }
+        alias M = A.inner;
+        alias X = Y;
+        alias Y = X;
+        function g() { M.f(); }",
+    );
+
+    assert_eq!(
+        call_targets(&database, "g"),
+        [Some(item(&database, "A.inner.f"))]
+    );
+    assert_eq!(diagnostics.len(), 1);
+    assert!(diagnostics[0].message.starts_with("alias cycle: "));
+}
+
+#[test]
+fn strict_self() {
+    let source = "module A { function f() { f(); self.f(); } }";
+
+    let (database, diagnostics) = resolve(source);
+    let f = item(&database, "A.f");
+    assert_eq!(diagnostics, []);
+    assert_eq!(call_targets(&database, "A.f"), [Some(f), Some(f)]);
+
+    let mut database = Database::new();
+    parse_source(&mut database, source).unwrap();
+    database.set_strict_self(true);
+    let diagnostics = database.resolve_idents();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(call_targets(&database, "A.f"), [None, Some(f)]);
+}
+
+#[test]
+fn grouped_imports() {
+    let (database, diagnostics) = resolve(
+        "module A {
+            pub module inner { pub function f() {} }
+            pub module other { pub function g() {} }
+        }
+        using A.{inner, other as renamed,};
+        function h() { inner.f(); renamed.g(); }",
+    );
+
+    assert_eq!(diagnostics, []);
+    assert_eq!(
+        call_targets(&database, "h"),
+        [
+            Some(item(&database, "A.inner.f")),
+            Some(item(&database, "A.other.g"))
+        ]
+    );
+}
+
+#[test]
+fn import_colliding_with_local() {
+    let (database, diagnostics) = resolve(
+        "module Other { pub function foo() {} }
+        module M {
+            function foo() {}
+            using crate.Other.foo;
+            function caller() { foo(); }
+        }",
+    );
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].message,
+        "import of `crate.Other.foo` is shadowed by local item `M.foo`"
+    );
+    assert_eq!(
+        call_targets(&database, "M.caller"),
+        [Some(item(&database, "M.foo"))]
+    );
+}
+
+#[test]
+fn raw_identifiers() {
+    let (database, diagnostics) = resolve("function r#module() {} function f() { r#module(); }");
+
+    assert_eq!(diagnostics, []);
+    let module = item(&database, "module");
+    assert_eq!(database.kind_of(module), ItemKind::Function);
+    assert_eq!(call_targets(&database, "f"), [Some(module)]);
+}
+
+#[test]
+fn suggestions() {
+    let (_, diagnostics) = resolve(
+        "module A { pub module inner { pub function f() {} } }
+        function caller() { A.innor.f(); g(); }",
+    );
+
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(
+        diagnostics[0].message,
+        "resolved `A` but `A` has no child `innor`; did you mean `inner`?"
+    );
+    // `A` is only one edit away, but a module can't be called.
+    assert_eq!(diagnostics[1].message, "symbol `g` not found");
+}
+
+#[test]
+fn glob_reexport() {
+    let (database, diagnostics) = resolve(
+        "module Impl { pub function f() {} pub function g() {} }
+        module Facade { pub using crate.Impl.*; }
+        module Hidden { using crate.Impl.*; }
+        function caller() { Facade.f(); Facade.g(); Hidden.f(); }",
+    );
+
+    assert_eq!(
+        call_targets(&database, "caller"),
+        [
+            Some(item(&database, "Impl.f")),
+            Some(item(&database, "Impl.g")),
+            None
+        ]
+    );
+    assert_eq!(diagnostics.len(), 1);
+}
+
+#[test]
+fn resolving_twice_is_the_same_as_once() {
+    let (mut database, first) = resolve(
+        "module A { pub function f() {} }
+        module B { pub using crate.A.*; }
+        using B.f;
+        using A as Renamed;
+        function g() { f(); Renamed.f(); missing(); }",
+    );
+    let table = database.symbol_table();
+    let bodies = database.resolved_ast_pretty();
+    // Imports are bound into the scopes, so they'd show up twice if they weren't cleared first.
+    let root_children = |database: &Database| {
+        let children = database.children_of(database.root());
+        children
+            .map(|(name, id)| (name.to_owned(), id))
+            .collect::<Vec<_>>()
+    };
+    let children = root_children(&database);
+
+    assert_eq!(database.resolve_idents(), first);
+    assert_eq!(database.symbol_table(), table);
+    assert_eq!(database.resolved_ast_pretty(), bodies);
+    assert_eq!(root_children(&database), children);
+}
+
+#[test]
+fn warn_empty() {
+    let source = "function empty() {} function full() { empty(); }";
+
+    let (_, diagnostics) = resolve(source);
+    assert_eq!(diagnostics, []);
+
+    let mut database = Database::new();
+    parse_source(&mut database, source).unwrap();
+    database.set_warn_empty(true);
+    let diagnostics = database.resolve_idents();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].message, "function `empty` is empty");
+    assert_eq!(diagnostics[0].severity, Severity::Warning);
+    assert_eq!(diagnostics[0].item, item(&database, "empty"));
+}
+
+#[test]
+fn self_paths() {
+    let (database, diagnostics) = resolve(
+        "module A {
+            module inner { pub function f() {} }
+            using self.inner.f;
+            function sibling() {}
+            function caller() { self.sibling(); f(); }
+        }",
+    );
+
+    // From a function, `self` is its module. The import is in the module's own scope, where
+    // `self` is the module itself.
+    assert_eq!(diagnostics, []);
+    assert_eq!(
+        call_targets(&database, "A.caller"),
+        [
+            Some(item(&database, "A.sibling")),
+            Some(item(&database, "A.inner.f"))
+        ]
+    );
+}
+
+#[test]
+fn super_imports() {
+    let (database, diagnostics) = resolve(
+        "module Parent {
+            module Sibling { pub function f() {} }
+            module Nested {
+                using super.Sibling;
+                function g() { Sibling.f(); }
+            }
+        }
+        using super.Nothing;",
+    );
+
+    assert_eq!(
+        call_targets(&database, "Parent.Nested.g"),
+        [Some(item(&database, "Parent.Sibling.f"))]
+    );
+    // There's nothing above the root.
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].item, database.root());
+}
+
+#[test]
+fn resolve_idents_checked() {
+    let mut database = Database::new();
+    parse_source(&mut database, "function f() {} function g() { f(); }").unwrap();
+    assert_eq!(database.resolve_idents_checked(), Ok(()));
+
+    let mut database = Database::new();
+    parse_source(&mut database, "function g() { f(); h(); }").unwrap();
+    assert_eq!(database.resolve_idents_checked().unwrap_err().len(), 2);
+}
+
+#[test]
+fn implicit_root_visibility() {
+    let source = "module Top { pub function f() {} }
+        module Outer { module Nested { function g() { Top.f(); crate.Top.f(); } } }";
+
+    let (database, diagnostics) = resolve(source);
+    let f = item(&database, "Top.f");
+    assert_eq!(diagnostics, []);
+    assert_eq!(
+        call_targets(&database, "Outer.Nested.g"),
+        [Some(f), Some(f)]
+    );
+
+    let mut database = Database::new();
+    parse_source(&mut database, source).unwrap();
+    database.set_implicit_root_visibility(false);
+    let diagnostics = database.resolve_idents();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].message,
+        "symbol `Top` not found; top-level items need to be imported, or named with `crate.Top`"
+    );
+    assert_eq!(call_targets(&database, "Outer.Nested.g"), [None, Some(f)]);
+}
+
+#[test]
+fn chained_calls() {
+    let (database, diagnostics) = resolve(
+        "module A {
+            pub function b() {
+                pub function c() { pub function d() {} }
+            }
+        }
+        function g() { A.b().c().d(); }",
+    );
+
+    assert_eq!(diagnostics, []);
+    let body = database.view().resolved_body(item(&database, "g"));
+    let ResolvedAST::Call { ident, chain, .. } = &body[0];
+    let targets: Vec<_> = std::iter::once(ident)
+        .chain(chain.iter().map(|link| &link.ident))
+        .map(|ident| match ident {
+            ResolvedIdent::Item(id) => database.full_path(*id),
+            ResolvedIdent::Unresolved(_) => String::new(),
+        })
+        .collect();
+    assert_eq!(targets, ["A.b", "A.b.c", "A.b.c.d"]);
+}
+
+#[test]
+fn glob_except() {
+    let (database, diagnostics) = resolve(
+        "module A { pub function f() {} pub function g() {} }
+        module B {
+            using A.* except g, missing;
+            function h() { f(); g(); }
+        }",
+    );
+
+    let f = item(&database, "A.f");
+    assert_eq!(call_targets(&database, "B.h"), [Some(f), None]);
+
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|d| (d.severity, d.message.as_str()))
+        .collect();
+    assert!(messages.contains(&(
+        Severity::Warning,
+        "`missing` is excluded from `A.*`, but `A` has no child `missing`"
+    )));
+    assert!(messages
+        .iter()
+        .any(|&(severity, message)| severity == Severity::Error && message.contains("`g`")));
+}
+
+#[test]
+fn local_shadows_import_in_either_order() {
+    for module in [
+        "module M { using crate.Other.foo; function foo() {} function caller() { foo(); } }",
+        "module M { function foo() {} using crate.Other.foo; function caller() { foo(); } }",
+    ] {
+        let (database, diagnostics) = resolve(&format!(
+            "module Other {{ pub function foo() {{}} }} {}",
+            module
+        ));
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(
+            diagnostics[0].message,
+            "import of `crate.Other.foo` is shadowed by local item `M.foo`"
+        );
+        assert_eq!(
+            call_targets(&database, "M.caller"),
+            [Some(item(&database, "M.foo"))]
+        );
+    }
+}
+
+#[test]
+fn skipped_items_are_not_resolved_or_visible() {
+    let (database, diagnostics) = resolve(
+        "#[skip] function f() { missing(); }
+        #[skip] module M { pub function h() { other(); } }
+        function g() { f(); M.h(); }",
+    );
+
+    // Only the calls naming the skipped items fail; their own bodies aren't looked at.
+    let mut messages: Vec<_> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+    messages.sort_unstable();
+    assert_eq!(
+        messages,
+        [
+            "symbol `M` not found",
+            "symbol `f` not found; did you mean `g`?"
+        ]
+    );
+    assert_eq!(call_targets(&database, "g"), [None, None]);
+}
+
+#[test]
+fn modules_and_functions_share_a_name() {
+    let (database, diagnostics) = resolve(
+        "module A { pub function f() {} }
+        function A() {}
+        function g() { A.f(); A(); }",
+    );
+
+    assert_eq!(diagnostics, []);
+    let named_a = |kind| {
+        let mut children = database.children_of(database.root());
+        children
+            .find(|&(name, id)| name == "A" && database.kind_of(id) == kind)
+            .unwrap()
+            .1
+    };
+    let function = named_a(ItemKind::Function);
+    assert_ne!(named_a(ItemKind::Module), function);
+
+    // Descending a path wants a module, while calling a bare name wants the function.
+    assert_eq!(
+        call_targets(&database, "g"),
+        [Some(item(&database, "A.f")), Some(function)]
+    );
+}
+
+#[test]
+fn crate_paths_skip_shadowing_items() {
+    let (database, diagnostics) = resolve(
+        "module A { pub function f() {} }
+        module B {
+            module A {}
+            using crate.A.f;
+            function g() { f(); crate.A.f(); }
+            function h() { crate.Missing.f(); }
+        }",
+    );
+
+    let f = item(&database, "A.f");
+    assert_eq!(call_targets(&database, "B.g"), [Some(f), Some(f)]);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].message,
+        "there is no top-level item `Missing`, in `crate.Missing.f`"
+    );
+}
+
+#[test]
+fn resolving_again_gives_the_same_results() {
+    // Imports and aliases are taken out of the database while they're resolved, so they have to
+    // be put back for the next time.
+    let (mut database, diagnostics) = resolve(
+        "module A { pub function f() {} pub module inner { pub function g() {} } }
+        using A.f;
+        using A.inner.*;
+        alias M = A.inner;
+        function h() { f(); g(); M.g(); missing(); }",
+    );
+    let resolved = database.resolved_ast_pretty();
+    assert_eq!(diagnostics.len(), 1);
+
+    assert_eq!(database.resolve_idents(), diagnostics);
+    assert_eq!(database.resolved_ast_pretty(), resolved);
+    let g = item(&database, "A.inner.g");
+    assert_eq!(
+        call_targets(&database, "h"),
+        [Some(item(&database, "A.f")), Some(g), Some(g), None]
+    );
+}
+
+#[test]
+fn trace_of_a_root_fallback() {
+    let mut database = Database::new();
+    parse_source(
+        &mut database,
+        "function top() {} module A { module B { function g() { top(); } } }",
+    )
+    .unwrap();
+    assert_eq!(database.take_trace(), Vec::<String>::new());
+
+    database.set_trace(true);
+    database.resolve_idents();
+    assert_eq!(
+        database.take_trace(),
+        [
+            "resolving `top` from `A.B.g`",
+            "checked-self: `A.B.g` isn't `top`",
+            "checked-children: no item `top` in `A.B.g`",
+            "checked-parent: no item `top` in `A.B`",
+            "checked-root: found item `top` in `<ROOT>`",
+        ]
+    );
+    // Taking the trace empties it.
+    assert_eq!(database.take_trace(), Vec::<String>::new());
+
+    database.set_trace(false);
+    database.resolve_idents();
+    assert_eq!(database.take_trace(), Vec::<String>::new());
+}
+
+#[test]
+fn named_crate() {
+    let (database, diagnostics) = resolve(
+        "crate MyCrate {
+            module A { pub function f() {} }
+            module B { module A {} function g() { crate.A.f(); } }
+        }",
+    );
+
+    assert_eq!(diagnostics, []);
+    assert_eq!(database.name_of(database.root()), "MyCrate");
+    // Paths still start below the root, whatever it's called.
+    assert_eq!(database.full_path(item(&database, "A.f")), "A.f");
+    assert_eq!(
+        call_targets(&database, "B.g"),
+        [Some(item(&database, "A.f"))]
+    );
+}
+
+#[test]
+fn import_shadowing_a_top_level_module() {
+    let (database, diagnostics) = resolve(
+        "module Util { pub function f() {} }
+        module Other { pub module Util { pub function f() {} } }
+        module M {
+            using crate.Other.Util;
+            function g() { Util.f(); }
+        }",
+    );
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Warning);
+    assert_eq!(
+        diagnostics[0].message,
+        "import of `crate.Other.Util` shadows the top-level module `Util` in `M`"
+    );
+    // The import still wins.
+    assert_eq!(
+        call_targets(&database, "M.g"),
+        [Some(item(&database, "Other.Util.f"))]
+    );
+}
+
+#[test]
+fn feature_guarded_imports() {
+    let source = "module A { pub function f() {} }
+        using A.f if fast;
+        function g() { f(); }";
+
+    // Off by default, so it's as if the import weren't there.
+    let (database, diagnostics) = resolve(source);
+    assert_eq!(diagnostics.len(), 1);
+    assert!(diagnostics[0].message.starts_with("symbol `f` not found"));
+    assert_eq!(call_targets(&database, "g"), [None]);
+
+    let mut database = Database::new();
+    parse_source(&mut database, source).unwrap();
+    database.enable_feature("fast");
+    assert_eq!(database.resolve_idents(), []);
+    assert_eq!(call_targets(&database, "g"), [Some(item(&database, "A.f"))]);
+}
+
+#[test]
+fn uses_of_a_failed_import_point_at_it() {
+    let (_, diagnostics) = resolve(
+        "module M {
+            using crate.Missing.x;
+            function g() { x(); }
+            function h() { x(); }
+        }",
+    );
+
+    let messages: Vec<_> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+    let from_import = "`x` comes from the import `crate.Missing.x`, which failed to resolve";
+    assert_eq!(messages.iter().filter(|&&m| m == from_import).count(), 2);
+    assert!(!messages
+        .iter()
+        .any(|m| m.starts_with("symbol `x` not found")));
+}
+
+#[test]
+fn double_colon_paths() {
+    let source = |separator: &str| {
+        format!(
+            "module A {{ pub module inner {{ pub function f() {{}} }} }}
+            using A{0}inner{0}f as g;
+            function h() {{ A{0}inner{0}f(); g(); }}",
+            separator
+        )
+    };
+
+    let (dotted, diagnostics) = resolve(&source("."));
+    assert_eq!(diagnostics, []);
+    let (colons, diagnostics) = resolve(&source("::"));
+    assert_eq!(diagnostics, []);
+
+    assert_eq!(call_targets(&colons, "h"), call_targets(&dotted, "h"));
+    assert_eq!(
+        call_targets(&colons, "h"),
+        [Some(item(&colons, "A.inner.f")); 2]
+    );
+}
+
+#[test]
+fn import_referring_back_to_its_own_module() {
+    let (_, diagnostics) = resolve(
+        "module A {
+            using crate.A;
+            module B { using crate.A; }
+        }",
+    );
+
+    let mut messages: Vec<_> = diagnostics
+        .iter()
+        .filter(|d| d.message.contains("refers back"))
+        .map(|d| d.message.as_str())
+        .collect();
+    messages.sort_unstable();
+    assert_eq!(
+        messages,
+        [
+            "import of `crate.A` in `A.B` refers back to `A`, which contains it",
+            "import of `crate.A` in `A` refers back to itself",
+        ]
+    );
+}
+
+#[test]
+fn sibling_modules() {
+    let (database, diagnostics) = resolve(
+        "module A {
+            module inner { pub function f() { other.g(); } }
+            module other { pub function g() {} }
+            function helper() {}
+            module third { function h() { inner.f(); helper(); } }
+        }",
+    );
+
+    assert_eq!(
+        call_targets(&database, "A.inner.f"),
+        [Some(item(&database, "A.other.g"))]
+    );
+    // Only modules are visible as siblings, not the parent's other items.
+    assert_eq!(
+        call_targets(&database, "A.third.h"),
+        [Some(item(&database, "A.inner.f")), None]
+    );
+    assert_eq!(diagnostics.len(), 1);
+    assert!(diagnostics[0]
+        .message
+        .starts_with("symbol `helper` not found"));
+}
+
+#[test]
+fn unresolved_imports_are_not_cycles_of_one() {
+    for (source, message) in [
+        (
+            "function alpha() {} module M { using alph; }",
+            "symbol `alph` not found; did you mean `alpha`?",
+        ),
+        (
+            "module Alpha {} module M { using Alph.*; }",
+            "symbol `Alph` not found; did you mean `Alpha`?",
+        ),
+        ("function f() { using Nope; }", "symbol `Nope` not found"),
+        ("using nope;", "symbol `nope` not found"),
+    ] {
+        let (_, diagnostics) = resolve(source);
+        let messages: Vec<_> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, [message], "{}", source);
+    }
+}